@@ -19,7 +19,7 @@ mkdir -p ${PACKAGE}/${ASSETS}
 
 echo `pwd`
 cp ${RELEASE_DIR}/${EXECUTABLE} ${PACKAGE}
-cp ${ASSETS}/* ${PACKAGE}/${ASSETS}
+cp -r ${ASSETS}/* ${PACKAGE}/${ASSETS}
 
 cd ${PACKAGE}
 zip -r ${ZIP_ARCHIVE} .
@@ -0,0 +1,255 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::{GameEvent, BOARD_HEIGHT, BOARD_WIDTH},
+    pieces::Cell,
+    render::Tile,
+};
+
+/// How long the rows where lines were cleared keep blinking.
+const LINE_CLEAR_DURATION: Duration = Duration::from_millis(240);
+const LINE_CLEAR_BLINK: Duration = Duration::from_millis(60);
+const LOCK_FLASH_DURATION: Duration = Duration::from_millis(90);
+const DROP_TRAIL_DURATION: Duration = Duration::from_millis(120);
+/// How long each row takes to grey out on game over, from the bottom up.
+const TOP_OUT_ROW_DURATION: Duration = Duration::from_millis(40);
+
+enum Animation {
+    LineClear {
+        rows: Vec<(usize, [Cell; BOARD_WIDTH])>,
+    },
+    LockFlash {
+        cells: Vec<(usize, usize)>,
+    },
+    DropTrail {
+        cells: Vec<(usize, usize)>,
+    },
+    TopOut,
+}
+
+impl Animation {
+    fn get_duration(&self) -> Duration {
+        match self {
+            Self::LineClear { .. } => LINE_CLEAR_DURATION,
+            Self::LockFlash { .. } => LOCK_FLASH_DURATION,
+            Self::DropTrail { .. } => DROP_TRAIL_DURATION,
+            Self::TopOut => TOP_OUT_ROW_DURATION * BOARD_HEIGHT as u32,
+        }
+    }
+}
+
+/// The animations running on a board. They only change how the board looks,
+/// never the board itself, so the game goes on while they play.
+#[derive(Default)]
+pub struct Animations {
+    running: Vec<(Instant, Animation)>,
+}
+
+impl Animations {
+    /// Starts the animation of something that happened on the board.
+    pub fn start(&mut self, event: GameEvent) {
+        let animation = match event {
+            GameEvent::HardDropped { from, to } => {
+                // the empty cells between where the piece was and where it
+                // landed, column by column
+                let cells = from
+                    .iter()
+                    .zip(&to)
+                    .flat_map(|(&(from_row, col), &(to_row, _))| {
+                        (from_row..to_row).map(move |row| (row, col))
+                    })
+                    .filter(|cell| !to.contains(cell))
+                    .collect();
+                Animation::DropTrail { cells }
+            }
+            GameEvent::PieceLocked { lock, .. } => Animation::LockFlash { cells: lock.cells },
+            GameEvent::LinesCleared { rows } => Animation::LineClear { rows },
+            GameEvent::PieceSpawned { .. } | GameEvent::PieceMoved { .. } => return,
+        };
+        self.start_at(Instant::now(), animation);
+    }
+
+    /// Greys the stack out row by row, and keeps it grey for good.
+    pub fn top_out(&mut self) {
+        self.start_at(Instant::now(), Animation::TopOut);
+    }
+
+    /// Tells whether any animation is still playing.
+    pub fn is_running(&self) -> bool {
+        let now = Instant::now();
+        self.running
+            .iter()
+            .any(|(started, animation)| now - *started < animation.get_duration())
+    }
+
+    /// Draws the animations over the tiles of the board.
+    pub fn apply(&self, tiles: &mut [Vec<Tile>]) {
+        self.apply_at(Instant::now(), tiles);
+    }
+
+    // Private functions
+
+    fn start_at(&mut self, now: Instant, animation: Animation) {
+        // the grey stack of a game over stays for good
+        self.running.retain(|(started, animation)| {
+            matches!(animation, Animation::TopOut) || now - *started < animation.get_duration()
+        });
+        self.running.push((now, animation));
+    }
+
+    fn apply_at(&self, now: Instant, tiles: &mut [Vec<Tile>]) {
+        for (started, animation) in &self.running {
+            let elapsed = now.saturating_duration_since(*started);
+            if elapsed >= animation.get_duration() && !matches!(animation, Animation::TopOut) {
+                continue;
+            }
+
+            match animation {
+                Animation::LineClear { rows } => {
+                    // the cleared rows blink where they were, over the live
+                    // board as the next piece is already falling
+                    let lit =
+                        (elapsed.as_millis() / LINE_CLEAR_BLINK.as_millis()).is_multiple_of(2);
+                    for (row, cells) in rows {
+                        for (tile, cell) in tiles[*row].iter_mut().zip(cells) {
+                            *tile = if lit { Tile::Flash } else { Tile::Cell(*cell) };
+                        }
+                    }
+                }
+                Animation::LockFlash { cells } => {
+                    for &(row, col) in cells {
+                        tiles[row][col] = Tile::Flash;
+                    }
+                }
+                Animation::DropTrail { cells } => {
+                    for &(row, col) in cells {
+                        if tiles[row][col] == Tile::Cell(Cell::Black) {
+                            tiles[row][col] = Tile::Trail;
+                        }
+                    }
+                }
+                Animation::TopOut => {
+                    let rows = (elapsed.as_millis() / TOP_OUT_ROW_DURATION.as_millis()) as usize;
+                    for row in tiles.iter_mut().rev().take(rows + 1) {
+                        for tile in row.iter_mut() {
+                            if matches!(tile, Tile::Cell(cell) if *cell != Cell::Black) {
+                                *tile = Tile::Cell(Cell::Gray);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::Board,
+        pieces::{Piece, Tetromino},
+        render,
+        utils::Input,
+    };
+
+    fn animate(board: &mut Board, animations: &mut Animations) {
+        for event in board.take_events() {
+            animations.start(event);
+        }
+    }
+
+    #[test]
+    fn line_clear_flashes_then_collapses() {
+        let mut board = Board::from_text("XXXXXX..XX\nXXXXXX..XX").unwrap();
+        board.add_piece(Piece::new(Tetromino::O, Tetromino::O.get_spawn_position()));
+        board.apply_input(Input::Right);
+        board.apply_input(Input::Right);
+        board.apply_input(Input::HardDrop);
+        assert_eq!(2, board.get_last_lock().lines);
+
+        let mut animations = Animations::default();
+        animate(&mut board, &mut animations);
+        let started = animations
+            .running
+            .iter()
+            .map(|(started, _)| *started)
+            .max()
+            .unwrap();
+
+        let mut tiles = render::board_tiles(&board);
+        animations.apply_at(started, &mut tiles);
+        assert_eq!(vec![Tile::Flash; BOARD_WIDTH], tiles[BOARD_HEIGHT - 1]);
+        // right above the landing spot, the piece left a trail
+        assert_eq!(Tile::Trail, tiles[10][6]);
+
+        // the rows blink over the live board, where the next piece shows
+        board.add_piece(Piece::new(Tetromino::T, Tetromino::T.get_spawn_position()));
+        let mut tiles = render::board_tiles(&board);
+        animations.apply_at(started + LINE_CLEAR_BLINK, &mut tiles);
+        assert_eq!(Tile::Cell(Cell::Gray), tiles[BOARD_HEIGHT - 1][0]);
+        assert_eq!(Tile::Cell(Cell::Yellow), tiles[BOARD_HEIGHT - 1][6]);
+        assert_eq!(Tile::Cell(Cell::Purple), tiles[0][4]);
+
+        let mut tiles = render::board_tiles(&board);
+        animations.apply_at(started + LINE_CLEAR_BLINK * 2, &mut tiles);
+        assert_eq!(vec![Tile::Flash; BOARD_WIDTH], tiles[BOARD_HEIGHT - 1]);
+        assert_eq!(Tile::Cell(Cell::Purple), tiles[0][4]);
+
+        let mut tiles = render::board_tiles(&board);
+        animations.apply_at(started + LINE_CLEAR_DURATION, &mut tiles);
+        assert_eq!(render::board_tiles(&board), tiles);
+    }
+
+    #[test]
+    fn line_clear_above_the_floor() {
+        let mut board = Board::from_text("XXXXXXXX..\nXXXXXXXXX.").unwrap();
+        board.add_piece(Piece::new(Tetromino::O, Tetromino::O.get_spawn_position()));
+        for _ in 0..4 {
+            board.apply_input(Input::Right);
+        }
+        board.apply_input(Input::HardDrop);
+        assert_eq!(1, board.get_last_lock().lines);
+
+        let mut animations = Animations::default();
+        animate(&mut board, &mut animations);
+        let started = animations.running.last().unwrap().0;
+        let cleared = BOARD_HEIGHT - 2;
+
+        let mut tiles = render::board_tiles(&board);
+        animations.apply_at(started, &mut tiles);
+        assert_eq!(vec![Tile::Flash; BOARD_WIDTH], tiles[cleared]);
+        assert_eq!(render::board_tiles(&board)[cleared + 1], tiles[cleared + 1]);
+
+        // between the flashes the cleared row shows as it was, not the half of
+        // the piece which fell in its place
+        let mut tiles = render::board_tiles(&board);
+        animations.apply_at(started + LINE_CLEAR_BLINK, &mut tiles);
+        let mut row = vec![Tile::Cell(Cell::Gray); 8];
+        row.extend([Tile::Cell(Cell::Yellow); 2]);
+        assert_eq!(row, tiles[cleared]);
+        assert_eq!(render::board_tiles(&board)[cleared + 1], tiles[cleared + 1]);
+
+        let tiles = render::board_tiles(&board);
+        assert_eq!(Tile::Cell(Cell::Black), tiles[cleared][0]);
+        assert_eq!(Tile::Cell(Cell::Yellow), tiles[cleared][8]);
+    }
+
+    #[test]
+    fn top_out_greys_the_stack_from_the_bottom() {
+        let board = Board::from_text("TTT.......\nIIII.OO...").unwrap();
+        let mut animations = Animations::default();
+        animations.top_out();
+        let started = animations.running[0].0;
+
+        let mut tiles = render::board_tiles(&board);
+        animations.apply_at(started, &mut tiles);
+        assert_eq!(Tile::Cell(Cell::Gray), tiles[BOARD_HEIGHT - 1][0]);
+        assert_eq!(Tile::Cell(Cell::Black), tiles[BOARD_HEIGHT - 1][4]);
+        assert_eq!(Tile::Cell(Cell::Purple), tiles[BOARD_HEIGHT - 2][0]);
+
+        let mut tiles = render::board_tiles(&board);
+        animations.apply_at(started + TOP_OUT_ROW_DURATION * 25, &mut tiles);
+        assert_eq!(Tile::Cell(Cell::Gray), tiles[BOARD_HEIGHT - 2][0]);
+    }
+}
@@ -1,15 +1,125 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+
 use crate::{
-    pieces::{Cell, Piece},
-    utils::{self, Direction, Rotation, Score},
+    pieces::{Cell, Piece, Tetromino},
+    utils::{self, Direction, Input, LockResult, Rotation, Score},
 };
 
-const BOARD_WIDTH: usize = 10;
-const BOARD_HEIGHT: usize = 20;
+pub const BOARD_WIDTH: usize = 10;
+pub const BOARD_HEIGHT: usize = 20;
 const LINE_CLEAR_POINTS: [u64; 5] = [0, 40, 100, 300, 1200];
 
+/// Rows and columns a piece on the board may still stick out by, the shapes
+/// being four cells wide.
+const PIECE_OVERHANG: usize = 4;
+/// Number of states of the placement search: every position of a piece on
+/// the board, in each of the four orientations, rotated into or not.
+const SEARCH_STATES: usize = (BOARD_HEIGHT + PIECE_OVERHANG) * (BOARD_WIDTH + PIECE_OVERHANG) * 8;
+
+/// Inputs explored when searching the placements, the hard drop is left out
+/// since it is the one final input of every placement.
+const SEARCH_INPUTS: [Input; 5] = [
+    Input::Left,
+    Input::Right,
+    Input::RotateClockwise,
+    Input::RotateCounterClockwise,
+    Input::SoftDrop,
+];
+
+/// A final position of a piece, reachable from where it entered the board.
+#[derive(Clone, Debug, Serialize)]
+pub struct Placement {
+    /// Cells taken by the piece once locked, in row major order.
+    pub cells: Vec<(usize, usize)>,
+    /// Shortest sequence of inputs reaching the placement, hard drop excluded.
+    /// Every soft drop here takes the piece one row down.
+    pub inputs: Vec<Input>,
+    /// Whether the piece locks with a T-spin.
+    pub tspin: bool,
+}
+
+impl Placement {
+    /// Number of keys pressed for the placement, soft drops in a row being
+    /// the same key held down.
+    pub fn count_inputs(&self) -> usize {
+        let repeated_drops = self
+            .inputs
+            .windows(2)
+            .filter(|pair| pair[0] == Input::SoftDrop && pair[1] == Input::SoftDrop)
+            .count();
+
+        self.inputs.len() - repeated_drops
+    }
+}
+
+/// Something that happened on the board, for the renderers to animate.
+/// The events of a piece are kept until the next piece gets added.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    /// A piece entered the board, its leftmost cell in this column.
+    PieceSpawned { tetromino: Tetromino, column: usize },
+    /// The active piece moved sideways or rotated.
+    PieceMoved {
+        column: usize,
+        orientation: Direction,
+    },
+    /// The active piece was hard dropped, from the cells it left.
+    HardDropped {
+        from: Vec<(usize, usize)>,
+        to: Vec<(usize, usize)>,
+    },
+    /// A piece locked, with the lines it cleared.
+    PieceLocked {
+        tetromino: Tetromino,
+        lock: LockResult,
+    },
+    /// Rows were completed and cleared, with their cells and their number
+    /// before the rows above fell in their place.
+    LinesCleared {
+        rows: Vec<(usize, [Cell; BOARD_WIDTH])>,
+    },
+}
+
+/// The active piece as the placement search moves it around, along with
+/// whether its last move was a rotation, for the T-spins.
+struct SearchState {
+    piece: Piece,
+    rotated: bool,
+    /// The state this one was reached from, by repeating an input some number
+    /// of times.
+    from: Option<(usize, Input, usize)>,
+}
+
+impl SearchState {
+    /// Returns the index of the state, below `SEARCH_STATES`.
+    fn key(&self) -> usize {
+        let pos = self.piece.get_position();
+        let (row, col) = utils::to_usize(
+            pos.row + PIECE_OVERHANG as isize,
+            pos.col + PIECE_OVERHANG as isize,
+        );
+        let orientation = match self.piece.get_orientation() {
+            Direction::Up => 0,
+            Direction::Right => 1,
+            Direction::Down => 2,
+            Direction::Left => 3,
+        };
+
+        ((row * (BOARD_WIDTH + PIECE_OVERHANG) + col) * 4 + orientation) * 2
+            + usize::from(self.rotated)
+    }
+}
+
+#[derive(Clone)]
 pub struct Board {
     board: [[Cell; BOARD_WIDTH]; BOARD_HEIGHT],
+    events: Vec<GameEvent>,
+    last_lock: LockResult,
+    last_move_rotation: bool,
     piece: Option<Piece>,
+    target: Vec<(usize, usize)>,
 }
 
 // Public functions
@@ -17,8 +127,58 @@ impl Board {
     pub fn new() -> Self {
         Self {
             board: [[Cell::default(); BOARD_WIDTH]; BOARD_HEIGHT],
+            events: Vec::new(),
+            last_lock: LockResult::default(),
+            last_move_rotation: false,
             piece: None,
+            target: Vec::new(),
+        }
+    }
+
+    /// Reads the board contents from text, having one line per row and one
+    /// character per cell (see `Cell::to_char`). When there are less lines than
+    /// rows, the lines fill the bottom of the board.
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let lines = text
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<&str>>();
+
+        if lines.len() > BOARD_HEIGHT {
+            return Err(format!(
+                "the board has {} rows, at most {BOARD_HEIGHT} are allowed",
+                lines.len()
+            ));
+        }
+
+        let mut board = Self::new();
+        let first_row = BOARD_HEIGHT - lines.len();
+        for (i, line) in lines.iter().enumerate() {
+            let cells = line
+                .chars()
+                .map(Cell::try_from)
+                .collect::<Result<Vec<Cell>, String>>()?;
+            if cells.len() != BOARD_WIDTH {
+                return Err(format!(
+                    "the board row '{line}' must have exactly {BOARD_WIDTH} cells"
+                ));
+            }
+            board.board[first_row + i].copy_from_slice(&cells);
         }
+
+        Ok(board)
+    }
+
+    /// Writes the board contents (without the active piece) as text, in the
+    /// format read by `Board::from_text`.
+    pub fn to_text(&self) -> String {
+        self.text(false)
+    }
+
+    /// Same as `Board::to_text`, with the active piece drawn on the board.
+    pub fn to_text_with_piece(&self) -> String {
+        self.text(true)
     }
 
     pub fn add_piece(&mut self, piece: Piece) -> bool {
@@ -26,7 +186,14 @@ impl Board {
             && Self::is_piece_on_the_board(&piece)
             && !Self::does_piece_overlap(&self.board, &piece)
         {
+            let tetromino = piece.get_tetromino().clone();
             self.piece = Some(piece);
+            self.last_move_rotation = false;
+            self.events.clear();
+            if let Some(column) = self.get_piece_column() {
+                self.events
+                    .push(GameEvent::PieceSpawned { tetromino, column });
+            }
             true
         } else {
             false
@@ -39,6 +206,7 @@ impl Board {
         if let Some(p) = self.piece.as_mut() {
             if Self::can_piece_slide(&self.board, p, &direction) {
                 p.slide(&direction);
+                self.last_move_rotation = false;
                 return (true, score);
             }
             if direction == Direction::Down {
@@ -49,6 +217,46 @@ impl Board {
         (false, score)
     }
 
+    /// Applies an input of the player to the active piece, returning the score
+    /// of the lock whenever the input locked the piece.
+    pub fn apply_input(&mut self, input: Input) -> Score {
+        match input {
+            Input::Left | Input::Right => {
+                let direction = if input == Input::Left {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                };
+                let (moved, score) = self.move_piece(direction);
+                if moved {
+                    self.piece_moved();
+                }
+                score
+            }
+            Input::SoftDrop => self.move_piece(Direction::Down).1,
+            Input::HardDrop => {
+                let from = self.piece.as_ref().map(Self::get_piece_cells);
+                let score = self.land_piece();
+                if let Some(from) = from {
+                    let to = self.last_lock.cells.clone();
+                    self.events.push(GameEvent::HardDropped { from, to });
+                }
+                score
+            }
+            Input::RotateClockwise | Input::RotateCounterClockwise => {
+                let rotation = if input == Input::RotateClockwise {
+                    Rotation::Clockwise
+                } else {
+                    Rotation::CounterClockwise
+                };
+                if self.rotate_piece(rotation) {
+                    self.piece_moved();
+                }
+                Score::default()
+            }
+        }
+    }
+
     pub fn land_piece(&mut self) -> Score {
         let mut lines_dropped = 0;
 
@@ -65,47 +273,409 @@ impl Board {
         }
     }
 
+    /// Moves the piece one row down, without ever locking it in place.
+    pub fn drop_piece(&mut self) -> bool {
+        self.move_piece_if_free(Direction::Down)
+    }
+
+    pub fn lock_piece(&mut self) -> Score {
+        self.incorporate_piece()
+    }
+
+    pub fn is_piece_grounded(&self) -> bool {
+        self.piece
+            .as_ref()
+            .is_some_and(|p| !Self::can_piece_slide(&self.board, p, &Direction::Down))
+    }
+
     pub fn rotate_piece(&mut self, rotation: Rotation) -> bool {
+        let rotated = self.rotate_with_kicks(&rotation);
+        if rotated {
+            self.last_move_rotation = true;
+        }
+
+        rotated
+    }
+
+    pub fn has_piece(&self) -> bool {
+        self.piece.is_some()
+    }
+
+    pub fn get_piece(&self) -> Option<&Piece> {
+        self.piece.as_ref()
+    }
+
+    /// Outlines the given cells on the board, as the placement to aim for. The
+    /// outline goes away once the active piece locks.
+    pub fn set_target(&mut self, cells: Vec<(usize, usize)>) {
+        self.target = cells;
+    }
+
+    /// Returns the cell at the given position, the active piece included.
+    pub fn get_cell(&self, row: usize, col: usize) -> Cell {
+        self.get_cell_at(row, col)
+    }
+
+    /// Whether the cell at the given position is part of the target outline.
+    pub fn is_target(&self, row: usize, col: usize) -> bool {
+        self.target.contains(&(row, col))
+    }
+
+    /// Returns the cells of the board, without the active piece.
+    pub fn get_grid(&self) -> &[[Cell; BOARD_WIDTH]; BOARD_HEIGHT] {
+        &self.board
+    }
+
+    pub fn get_last_lock(&self) -> &LockResult {
+        &self.last_lock
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..BOARD_HEIGHT).all(|row| self.is_row_empty(row))
+    }
+
+    /// Returns what happened on the board since the last call.
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn take_piece(&mut self) -> Option<Piece> {
+        self.piece.take()
+    }
+
+    /// Pushes the given rows into the bottom of the board, raising the existing
+    /// stack by the same amount. The first row in the slice ends up topmost.
+    /// The active piece is lifted as well, whenever the raised stack would
+    /// otherwise overlap it.
+    ///
+    /// Returns false if any part of the stack, or the active piece, was pushed
+    /// through the ceiling.
+    pub fn insert_rows(&mut self, rows: &[[Cell; BOARD_WIDTH]]) -> bool {
+        let count = rows.len().min(BOARD_HEIGHT);
+        let overflow = (0..count).any(|row| !self.is_row_empty(row));
+
+        for row in 0..BOARD_HEIGHT - count {
+            self.board[row] = self.board[row + count];
+        }
+        for (offset, row) in rows.iter().rev().take(count).enumerate() {
+            self.board[BOARD_HEIGHT - 1 - offset] = *row;
+        }
+
         if let Some(p) = self.piece.as_mut() {
-            // Check if 'in-place' rotation is allowed, and rotate if true
-            if Self::can_piece_rotate(&self.board, p, &rotation) {
-                p.rotate(&rotation);
-                return true;
+            while Self::is_piece_on_the_board(p) && Self::does_piece_overlap(&self.board, p) {
+                p.slide(&Direction::Up);
             }
-            if Self::can_piece_slide(&self.board, p, &Direction::Left) {
-                p.slide(&Direction::Left);
-                // Try sliding the piece to the left, and attempt a rotation there
-                if Self::can_piece_rotate(&self.board, p, &rotation) {
-                    p.rotate(&rotation);
-                    return true;
-                }
-                p.slide(&Direction::Right); // undo the slide
+            if !Self::is_piece_on_the_board(p) {
+                return false;
+            }
+        }
+
+        !overflow
+    }
+
+    /// Raises the stack by the given number of garbage rows, all of them having
+    /// their hole in the same column.
+    pub fn insert_garbage(&mut self, rows: usize, hole_col: usize) -> bool {
+        self.insert_rows(&vec![Self::garbage_row(hole_col); rows])
+    }
+
+    /// Builds a full garbage row, with a single hole at the given column.
+    pub fn garbage_row(hole_col: usize) -> [Cell; BOARD_WIDTH] {
+        let mut row = [Cell::Gray; BOARD_WIDTH];
+        if hole_col < BOARD_WIDTH {
+            row[hole_col] = Cell::Black;
+        }
+
+        row
+    }
+
+    pub fn count_cells(&self) -> usize {
+        self.board
+            .iter()
+            .flatten()
+            .filter(|cell| **cell != Cell::Black)
+            .count()
+    }
+
+    /// Returns the number of rows between the floor and the highest cell of the
+    /// stack, the active piece is not part of the stack.
+    pub fn get_stack_height(&self) -> usize {
+        (0..BOARD_HEIGHT)
+            .find(|row| !self.is_row_empty(*row))
+            .map_or(0, |row| BOARD_HEIGHT - row)
+    }
+
+    /// Returns the height of every column, from the floor to its highest cell.
+    pub fn get_column_heights(&self) -> [usize; BOARD_WIDTH] {
+        let mut heights = [0; BOARD_WIDTH];
+        for (col, height) in heights.iter_mut().enumerate() {
+            *height = (0..BOARD_HEIGHT)
+                .find(|row| self.board[*row][col] != Cell::Black)
+                .map_or(0, |row| BOARD_HEIGHT - row);
+        }
+
+        heights
+    }
+
+    /// Counts the empty cells having some cell of the stack above them.
+    pub fn count_holes(&self) -> usize {
+        let heights = self.get_column_heights();
+
+        (0..BOARD_WIDTH)
+            .map(|col| {
+                (BOARD_HEIGHT - heights[col]..BOARD_HEIGHT)
+                    .filter(|row| self.board[*row][col] == Cell::Black)
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Sums up the height differences between neighbouring columns.
+    pub fn get_bumpiness(&self) -> usize {
+        self.get_column_heights()
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .sum()
+    }
+
+    /// Returns the depth of the well at every column, that is how far the
+    /// column is below both of its neighbours. The walls count as full columns.
+    pub fn get_well_depths(&self) -> [usize; BOARD_WIDTH] {
+        let heights = self.get_column_heights();
+        let mut depths = [0; BOARD_WIDTH];
+
+        for (col, depth) in depths.iter_mut().enumerate() {
+            let left = if col == 0 {
+                BOARD_HEIGHT
+            } else {
+                heights[col - 1]
+            };
+            let right = heights.get(col + 1).copied().unwrap_or(BOARD_HEIGHT);
+            *depth = left.min(right).saturating_sub(heights[col]);
+        }
+
+        depths
+    }
+
+    /// Finds every placement the given piece can lock at, starting from its
+    /// position, along with the shortest inputs leading there. Slides and
+    /// rotations are searched from every position the piece can reach, at
+    /// every height, so tucks and spins relying on kicks are found too. Soft
+    /// drops in a row count as a single input, the key being held down.
+    /// Pieces locking on the same cells are the same placement, whatever their
+    /// orientation, unless only one of them is a T-spin.
+    pub fn legal_placements(&self, piece: &Piece) -> Vec<Placement> {
+        let mut placements: Vec<Placement> = Vec::new();
+        if !Self::is_piece_on_the_board(piece) || Self::does_piece_overlap(&self.board, piece) {
+            return placements;
+        }
+
+        let start = SearchState {
+            piece: piece.clone(),
+            rotated: false,
+            from: None,
+        };
+        let mut visited = vec![None; SEARCH_STATES];
+        visited[start.key()] = Some(0);
+        let mut states = vec![start];
+        let mut queue = VecDeque::from([0]);
+
+        // breadth first, the first time a placement shows up is the shortest one
+        while let Some(index) = queue.pop_front() {
+            let (cells, tspin) = self.land(&states[index]);
+            if !placements
+                .iter()
+                .any(|p| p.cells == cells && p.tspin == tspin)
+            {
+                placements.push(Placement {
+                    cells,
+                    inputs: Self::search_path(&states, index),
+                    tspin,
+                });
             }
-            if Self::can_piece_slide(&self.board, p, &Direction::Right) {
-                p.slide(&Direction::Right);
-                // Try sliding the piece to the right, and attempt a rotation there
-                if Self::can_piece_rotate(&self.board, p, &rotation) {
-                    p.rotate(&rotation);
-                    return true;
+
+            for input in SEARCH_INPUTS {
+                // a soft drop may be held down for any number of rows, all of
+                // them the same single input
+                let mut next = self.search_step(&states[index], input);
+                let mut repeats = 0;
+                while let Some(mut moved) = next {
+                    repeats += 1;
+                    moved.from = Some((index, input, repeats));
+                    next = (input == Input::SoftDrop)
+                        .then(|| self.search_step(&moved, input))
+                        .flatten();
+
+                    match visited[moved.key()] {
+                        // an earlier soft drop went through, and further down
+                        Some(seen)
+                            if matches!(states[seen].from, Some((_, Input::SoftDrop, _))) =>
+                        {
+                            break;
+                        }
+                        Some(_) => {}
+                        None => {
+                            visited[moved.key()] = Some(states.len());
+                            queue.push_back(states.len());
+                            states.push(moved);
+                        }
+                    }
                 }
-                p.slide(&Direction::Left); // undo the slide
             }
         }
 
-        false
+        placements
     }
 
-    pub fn has_piece(&self) -> bool {
-        self.piece.is_some()
+    pub fn count_garbage_rows(&self) -> usize {
+        self.board
+            .iter()
+            .filter(|row| row.contains(&Cell::Gray))
+            .count()
     }
 }
 
 // Private functions
 impl Board {
+    fn text(&self, with_piece: bool) -> String {
+        (0..BOARD_HEIGHT)
+            .map(|row| {
+                (0..BOARD_WIDTH)
+                    .map(|col| {
+                        if with_piece {
+                            self.get_cell_at(row, col).to_char()
+                        } else {
+                            self.board[row][col].to_char()
+                        }
+                    })
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
+    }
+
     fn remove_piece(&mut self) {
         self.piece = None;
     }
 
+    fn rotate_with_kicks(&mut self, rotation: &Rotation) -> bool {
+        match self.piece.as_mut() {
+            Some(p) => Self::kick(&self.board, p, rotation),
+            None => false,
+        }
+    }
+
+    fn kick(
+        board: &[[Cell; BOARD_WIDTH]; BOARD_HEIGHT],
+        p: &mut Piece,
+        rotation: &Rotation,
+    ) -> bool {
+        // Check if 'in-place' rotation is allowed, and rotate if true
+        if Self::can_piece_rotate(board, p, rotation) {
+            p.rotate(rotation);
+            return true;
+        }
+        if Self::can_piece_slide(board, p, &Direction::Left) {
+            p.slide(&Direction::Left);
+            // Try sliding the piece to the left, and attempt a rotation there
+            if Self::can_piece_rotate(board, p, rotation) {
+                p.rotate(rotation);
+                return true;
+            }
+            p.slide(&Direction::Right); // undo the slide
+        }
+        if Self::can_piece_slide(board, p, &Direction::Right) {
+            p.slide(&Direction::Right);
+            // Try sliding the piece to the right, and attempt a rotation there
+            if Self::can_piece_rotate(board, p, rotation) {
+                p.rotate(rotation);
+                return true;
+            }
+            p.slide(&Direction::Left); // undo the slide
+        }
+
+        false
+    }
+
+    /// Moves the searched piece with an input, as long as the input moves it
+    /// at all. A soft drop never locks the piece here.
+    fn search_step(&self, state: &SearchState, input: Input) -> Option<SearchState> {
+        let mut piece = state.piece.clone();
+        let moved = match input {
+            Input::Left => Some(Direction::Left),
+            Input::Right => Some(Direction::Right),
+            Input::SoftDrop => Some(Direction::Down),
+            _ => None,
+        };
+
+        if let Some(direction) = moved {
+            if !Self::can_piece_slide(&self.board, &piece, &direction) {
+                return None;
+            }
+            piece.slide(&direction);
+        } else {
+            let rotation = if input == Input::RotateClockwise {
+                Rotation::Clockwise
+            } else {
+                Rotation::CounterClockwise
+            };
+            if !Self::kick(&self.board, &mut piece, &rotation) {
+                return None;
+            }
+        }
+
+        Some(SearchState {
+            piece,
+            rotated: moved.is_none(),
+            from: None,
+        })
+    }
+
+    /// Returns the inputs leading the search from the first state to the given
+    /// one.
+    fn search_path(states: &[SearchState], mut index: usize) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        while let Some((parent, input, repeats)) = states[index].from {
+            inputs.extend(std::iter::repeat_n(input, repeats));
+            index = parent;
+        }
+        inputs.reverse();
+
+        inputs
+    }
+
+    /// Returns the cells the searched piece locks on once hard dropped, and
+    /// whether it locks with a T-spin.
+    fn land(&self, state: &SearchState) -> (Vec<(usize, usize)>, bool) {
+        let mut piece = state.piece.clone();
+        let mut rotated = state.rotated;
+        loop {
+            piece.slide(&Direction::Down);
+            if Self::does_piece_overlap(&self.board, &piece) {
+                piece.slide(&Direction::Up);
+                break;
+            }
+            rotated = false;
+        }
+
+        (
+            Self::get_piece_cells(&piece),
+            rotated && self.is_tspin(&piece),
+        )
+    }
+
+    fn move_piece_if_free(&mut self, direction: Direction) -> bool {
+        if let Some(p) = self.piece.as_mut()
+            && Self::can_piece_slide(&self.board, p, &direction)
+        {
+            p.slide(&direction);
+            self.last_move_rotation = false;
+            true
+        } else {
+            false
+        }
+    }
+
     fn can_piece_slide(
         board: &[[Cell; BOARD_WIDTH]; BOARD_HEIGHT],
         piece: &Piece,
@@ -138,6 +708,9 @@ impl Board {
 
     fn incorporate_piece(&mut self) -> Score {
         if let Some(piece) = &self.piece {
+            let tetromino = piece.get_tetromino().clone();
+            let tspin = self.last_move_rotation && self.is_tspin(piece);
+            let cells = Self::get_piece_cells(piece);
             let pos = piece.get_position();
 
             for row in 0..piece.get_size() {
@@ -152,14 +725,60 @@ impl Board {
             }
 
             self.remove_piece();
-
-            self.collapse_completed_rows()
+            self.target.clear();
+
+            // the lock comes before the lines it clears
+            let event = self.events.len();
+            let score = self.collapse_completed_rows();
+            self.last_lock = LockResult {
+                cells,
+                lines: score.lines_destroyed,
+                tspin,
+                perfect_clear: score.lines_destroyed > 0 && self.is_empty(),
+            };
+            let lock = self.last_lock.clone();
+            self.events
+                .insert(event, GameEvent::PieceLocked { tetromino, lock });
+
+            score
         } else {
             Score::default()
         }
     }
 
+    /// A T piece which got into place by a rotation is spinning, whenever at
+    /// least three corners around its center are blocked.
+    fn is_tspin(&self, piece: &Piece) -> bool {
+        if *piece.get_tetromino() != Tetromino::T {
+            return false;
+        }
+
+        let pos = piece.get_position();
+        let blocked_corners = [(0, 0), (0, 2), (2, 0), (2, 2)]
+            .iter()
+            .filter(|(row, col)| {
+                let (i_br, i_bc) = utils::to_board_coord(pos, *row, *col);
+                if Self::inside_board(i_br, i_bc) {
+                    let (u_br, u_bc) = utils::to_usize(i_br, i_bc);
+                    self.board[u_br][u_bc] != Cell::Black
+                } else {
+                    true
+                }
+            })
+            .count();
+
+        blocked_corners >= 3
+    }
+
     fn collapse_completed_rows(&mut self) -> Score {
+        let rows = (1..BOARD_HEIGHT)
+            .filter(|row| self.is_row_full(*row))
+            .map(|row| (row, self.board[row]))
+            .collect::<Vec<(usize, [Cell; BOARD_WIDTH])>>();
+        if !rows.is_empty() {
+            self.events.push(GameEvent::LinesCleared { rows });
+        }
+
         let mut cleared_lines = 0;
 
         loop {
@@ -186,6 +805,35 @@ impl Board {
         }
     }
 
+    /// Returns the leftmost column of the active piece.
+    fn get_piece_column(&self) -> Option<usize> {
+        let piece = self.piece.as_ref()?;
+        Self::get_piece_cells(piece)
+            .into_iter()
+            .map(|(_, col)| col)
+            .min()
+    }
+
+    fn piece_moved(&mut self) {
+        if let Some(column) = self.get_piece_column()
+            && let Some(piece) = self.piece.as_ref()
+        {
+            let orientation = *piece.get_orientation();
+            self.events.push(GameEvent::PieceMoved {
+                column,
+                orientation,
+            });
+        }
+    }
+
+    fn get_piece_cells(piece: &Piece) -> Vec<(usize, usize)> {
+        piece
+            .get_cells()
+            .into_iter()
+            .map(|(row, col)| utils::to_usize(row, col))
+            .collect()
+    }
+
     fn is_row_full(&self, row: usize) -> bool {
         for col in 0..BOARD_WIDTH {
             if self.get_cell_at(row, col) == Cell::Black {
@@ -196,6 +844,10 @@ impl Board {
         true
     }
 
+    fn is_row_empty(&self, row: usize) -> bool {
+        self.board[row].iter().all(|cell| *cell == Cell::Black)
+    }
+
     fn lower_row(&mut self, row: usize) {
         for col in 0..BOARD_WIDTH {
             if row == 0 {
@@ -276,26 +928,6 @@ impl Board {
     }
 }
 
-impl std::fmt::Display for Board {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in 0..BOARD_HEIGHT {
-            let mut line = String::new();
-            for col in 0..BOARD_WIDTH {
-                let cell = self.get_cell_at(row, col);
-                line = format!("{line}{cell}");
-            }
-            let _ = writeln!(f, "🧱{line}🧱");
-        }
-
-        let mut bottom = String::new();
-        for _ in 0..BOARD_WIDTH {
-            bottom = format!("{bottom}🧱");
-        }
-
-        writeln!(f, "🧱{bottom}🧱")
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use utils::Position;
@@ -597,4 +1229,312 @@ mod tests {
         assert_eq!(Cell::Blue, board.get_cell_at(19, 8));
         assert_eq!(Cell::Brown, board.get_cell_at(19, 9));
     }
+
+    #[test]
+    fn insert_rows_raises_stack() {
+        let mut board = Board::new();
+
+        let pos = Position { row: 16, col: 3 };
+        let piece_o = Piece::new(crate::pieces::Tetromino::O, pos);
+        assert!(board.add_piece(piece_o));
+        board.incorporate_piece();
+
+        let rows = [Board::garbage_row(0), Board::garbage_row(9)];
+        assert!(board.insert_rows(&rows));
+
+        assert_eq!(Cell::Yellow, board.get_cell_at(15, 4));
+        assert_eq!(Cell::Yellow, board.get_cell_at(16, 5));
+        assert_eq!(Cell::Black, board.get_cell_at(18, 0));
+        assert_eq!(Cell::Gray, board.get_cell_at(18, 9));
+        assert_eq!(Cell::Gray, board.get_cell_at(19, 0));
+        assert_eq!(Cell::Black, board.get_cell_at(19, 9));
+        assert_eq!(2, board.count_garbage_rows());
+        assert_eq!(22, board.count_cells());
+        assert_eq!(5, board.get_stack_height());
+    }
+
+    #[test]
+    fn insert_rows_through_ceiling() {
+        let mut board = Board::new();
+
+        let rows = [Board::garbage_row(4); BOARD_HEIGHT];
+        assert!(board.insert_rows(&rows));
+        assert!(!board.insert_rows(&[Board::garbage_row(4)]));
+    }
+
+    #[test]
+    fn clearing_garbage_rows() {
+        let mut board = Board::new();
+
+        assert!(board.insert_rows(&[Board::garbage_row(0), Board::garbage_row(0)]));
+        assert_eq!(2, board.count_garbage_rows());
+
+        let pos = Position { row: 16, col: -1 };
+        let piece_i = Piece::new(crate::pieces::Tetromino::I, pos);
+        assert!(board.add_piece(piece_i));
+        board.incorporate_piece();
+
+        assert_eq!(0, board.count_garbage_rows());
+    }
+
+    #[test]
+    fn insert_garbage_lifts_piece() {
+        let mut board = Board::new();
+
+        let pos = Position { row: 16, col: 3 };
+        let piece_o = Piece::new(crate::pieces::Tetromino::O, pos);
+        assert!(board.add_piece(piece_o));
+
+        assert!(board.insert_garbage(2, 0));
+        assert_eq!(Cell::Yellow, board.get_cell_at(16, 4));
+        assert_eq!(Cell::Yellow, board.get_cell_at(17, 5));
+        assert_eq!(Cell::Gray, board.get_cell_at(18, 4));
+
+        assert!(!board.move_piece(Direction::Down).0);
+        assert_eq!(2, board.count_garbage_rows());
+    }
+
+    #[test]
+    fn insert_garbage_keeps_floating_piece() {
+        let mut board = Board::new();
+
+        let pos = Position { row: 5, col: 3 };
+        let piece_o = Piece::new(crate::pieces::Tetromino::O, pos);
+        assert!(board.add_piece(piece_o));
+
+        assert!(board.insert_garbage(3, 9));
+        assert_eq!(Cell::Yellow, board.get_cell_at(6, 4));
+    }
+
+    #[test]
+    fn insert_garbage_tops_out_piece() {
+        let mut board = Board::new();
+
+        assert!(board.insert_garbage(BOARD_HEIGHT - 3, 0));
+
+        let pos = Position { row: 0, col: 3 };
+        let piece_o = Piece::new(crate::pieces::Tetromino::O, pos);
+        assert!(board.add_piece(piece_o));
+
+        assert!(board.insert_garbage(1, 0));
+        assert!(!board.insert_garbage(1, 0));
+    }
+
+    #[test]
+    fn drop_piece_without_locking() {
+        let mut board = Board::new();
+
+        let pos = Position { row: 16, col: 3 };
+        let piece_o = Piece::new(crate::pieces::Tetromino::O, pos);
+        assert!(board.add_piece(piece_o));
+
+        assert!(!board.is_piece_grounded());
+        assert!(board.drop_piece());
+        assert!(board.is_piece_grounded());
+        assert!(!board.drop_piece());
+        assert!(board.has_piece());
+
+        board.lock_piece();
+        assert!(!board.has_piece());
+        assert_eq!(Cell::Yellow, board.get_cell_at(19, 4));
+    }
+
+    #[test]
+    fn snapshot_restores_board() {
+        let mut board = Board::new();
+
+        let pos = Position { row: 16, col: 3 };
+        let piece_o = Piece::new(crate::pieces::Tetromino::O, pos);
+        assert!(board.add_piece(piece_o));
+        let snapshot = board.clone();
+
+        board.land_piece();
+        assert!(!board.has_piece());
+        assert_eq!(Cell::Yellow, board.get_cell_at(19, 4));
+
+        board = snapshot;
+        assert!(board.has_piece());
+        assert_eq!(Cell::Yellow, board.get_cell_at(17, 4));
+        assert_eq!(Cell::Black, board.get_cell_at(19, 4));
+    }
+
+    #[test]
+    fn board_text_round_trip() {
+        let text = "XXXX......\nXXX...XXXX\nXXXX.XXXXX\n";
+        let board = Board::from_text(text).unwrap();
+
+        assert_eq!(Cell::Black, board.get_cell_at(16, 0));
+        assert_eq!(Cell::Gray, board.get_cell_at(17, 3));
+        assert_eq!(Cell::Black, board.get_cell_at(18, 3));
+        assert_eq!(Cell::Black, board.get_cell_at(19, 4));
+        assert!(board.to_text().ends_with(text));
+        assert_eq!(BOARD_HEIGHT, board.to_text().lines().count());
+
+        assert!(Board::from_text("XXXX.....\n").is_err());
+        assert!(Board::from_text("XXXX.....?\n").is_err());
+    }
+
+    #[test]
+    fn tspin_double() {
+        let mut board = Board::from_text("XXXX......\nXXX...XXXX\nXXXX.XXXXX\n").unwrap();
+
+        let pos = Position { row: 16, col: 3 };
+        let mut piece_t = Piece::new(crate::pieces::Tetromino::T, pos);
+        piece_t.rotate(&Rotation::CounterClockwise);
+        assert!(board.add_piece(piece_t));
+        assert!(board.drop_piece());
+        assert!(board.is_piece_grounded());
+
+        assert!(board.rotate_piece(Rotation::Clockwise));
+        board.land_piece();
+
+        let lock = board.get_last_lock();
+        assert_eq!(2, lock.lines);
+        assert!(lock.tspin);
+        assert!(!lock.perfect_clear);
+    }
+
+    #[test]
+    fn perfect_clear() {
+        let mut board = Board::from_text("XXXXXXXX..\nXXXXXXXX..\n").unwrap();
+
+        let pos = Position { row: 17, col: 7 };
+        let piece_o = Piece::new(crate::pieces::Tetromino::O, pos);
+        assert!(board.add_piece(piece_o));
+        board.land_piece();
+
+        let lock = board.get_last_lock();
+        assert_eq!(2, lock.lines);
+        assert!(!lock.tspin);
+        assert!(lock.perfect_clear);
+        assert!(board.is_empty());
+    }
+
+    #[test]
+    fn board_metrics() {
+        let board = Board::from_text("X.........\nXX.X.....X\nX.XX.X...X\nXXXX.XX..X").unwrap();
+
+        assert_eq!([4, 3, 2, 3, 0, 2, 1, 0, 0, 3], board.get_column_heights());
+        assert_eq!(1, board.count_holes());
+        assert_eq!(13, board.get_bumpiness());
+        assert_eq!([0, 0, 1, 0, 2, 0, 0, 0, 0, 0], board.get_well_depths());
+        assert_eq!(4, board.get_stack_height());
+    }
+
+    fn placements_of(board: &Board, tetromino: Tetromino) -> Vec<Placement> {
+        let position = tetromino.get_spawn_position();
+        board.legal_placements(&Piece::new(tetromino, position))
+    }
+
+    #[test]
+    fn symmetric_placements() {
+        let board = Board::new();
+
+        // both flat and upright positions, each orientation counted once
+        for (tetromino, count) in [
+            (Tetromino::O, 9),
+            (Tetromino::I, 17),
+            (Tetromino::S, 17),
+            (Tetromino::Z, 17),
+            (Tetromino::T, 34),
+            (Tetromino::J, 34),
+            (Tetromino::L, 34),
+        ] {
+            assert_eq!(
+                count,
+                placements_of(&board, tetromino.clone()).len(),
+                "{tetromino:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn placements_replay_their_inputs() {
+        let board = Board::from_text("XX......XX\nXXX....XXX\nXXXX..XXXX").unwrap();
+
+        for placement in placements_of(&board, Tetromino::S) {
+            let mut board = board.clone();
+            let position = Tetromino::S.get_spawn_position();
+            board.add_piece(Piece::new(Tetromino::S, position));
+            for input in &placement.inputs {
+                if *input == Input::SoftDrop {
+                    board.drop_piece();
+                } else {
+                    board.apply_input(*input);
+                }
+            }
+            board.land_piece();
+
+            assert_eq!(placement.cells, board.get_last_lock().cells);
+        }
+    }
+
+    #[test]
+    fn tuck_under_overhang() {
+        // the I piece only gets below the overhang by dropping first
+        let board = Board::from_text("XXX.......\n..........").unwrap();
+        let tucked = vec![(19, 0), (19, 1), (19, 2), (19, 3)];
+        let placement = placements_of(&board, Tetromino::I)
+            .into_iter()
+            .find(|p| p.cells == tucked)
+            .unwrap();
+        assert!(placement.inputs.contains(&Input::SoftDrop));
+        assert_eq!(Some(&Input::Left), placement.inputs.last());
+    }
+
+    #[test]
+    fn tspin_placement() {
+        let board = Board::from_text("XX........\nX...XXXXXX\nXX.XXXXXXX").unwrap();
+
+        let placements = placements_of(&board, Tetromino::T);
+        let spin = placements.iter().find(|p| p.tspin).unwrap();
+        assert_eq!(vec![(18, 1), (18, 2), (18, 3), (19, 2)], spin.cells);
+    }
+
+    #[test]
+    fn tspin_into_slide_slot() {
+        // the T slides under the overhang, or spins into the same cells
+        let board = Board::from_text(".X.X......\n..........\nXX......X.").unwrap();
+        let slot = vec![(18, 1), (18, 2), (18, 3), (19, 2)];
+
+        let spins = placements_of(&board, Tetromino::T)
+            .into_iter()
+            .filter(|p| p.cells == slot)
+            .map(|p| p.tspin)
+            .collect::<Vec<bool>>();
+        assert_eq!(vec![false, true], spins);
+    }
+
+    #[test]
+    fn slide_at_mid_height() {
+        // the O piece only gets into the pocket by stopping its drop halfway
+        let board =
+            Board::from_text("XX........\n..........\n..........\nXX..XXXXXX\nXX..XXXXXX").unwrap();
+        let pocket = vec![(16, 0), (16, 1), (17, 0), (17, 1)];
+
+        let placement = placements_of(&board, Tetromino::O)
+            .into_iter()
+            .find(|p| p.cells == pocket)
+            .unwrap();
+        assert_eq!(5, placement.count_inputs());
+        assert_eq!(Some(&Input::Left), placement.inputs.last());
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --ignored --nocapture"]
+    fn bench_legal_placements() {
+        let board = Board::from_text("X.........\nXX.X.....X\nX.XX.X...X\nXXXX.XX..X").unwrap();
+        let runs = 1000;
+
+        let started = std::time::Instant::now();
+        for _ in 0..runs {
+            for tetromino in [Tetromino::I, Tetromino::O, Tetromino::T, Tetromino::L] {
+                placements_of(&board, tetromino);
+            }
+        }
+        println!(
+            "legal_placements: {:?} per piece",
+            started.elapsed() / (runs * 4)
+        );
+    }
 }
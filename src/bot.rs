@@ -0,0 +1,346 @@
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, Placement},
+    pieces::{Piece, Tetromino},
+    tbp,
+    utils::{Attack, Input},
+};
+
+/// How fast the bot plays, as a cap on the pieces it places per second.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BotLevel {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+    Max,
+}
+
+impl BotLevel {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Easy => "EASY",
+            Self::Medium => "MEDIUM",
+            Self::Hard => "HARD",
+            Self::Max => "MAX",
+        }
+    }
+
+    pub fn get_pieces_per_second(&self) -> f64 {
+        match self {
+            Self::Easy => 0.75,
+            Self::Medium => 1.5,
+            Self::Hard => 3.0,
+            Self::Max => 8.0,
+        }
+    }
+}
+
+impl FromStr for BotLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Self::Easy),
+            "medium" => Ok(Self::Medium),
+            "hard" => Ok(Self::Hard),
+            "max" => Ok(Self::Max),
+            _ => Err(format!(
+                "invalid ai level '{s}', expected 'easy', 'medium', 'hard' or 'max'"
+            )),
+        }
+    }
+}
+
+/// Weights of the board features evaluated by the bot, a placement scores the
+/// weighted sum of the features of the board it leaves behind.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weights {
+    pub height: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+    pub wells: f64,
+    pub lines: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            height: -0.51,
+            holes: -0.36,
+            bumpiness: -0.18,
+            wells: -0.1,
+            lines: 0.76,
+        }
+    }
+}
+
+/// Reads weights written as `height=-0.5,holes=-0.4`, the weights left out
+/// keep their default value.
+impl FromStr for Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Self::default();
+
+        for pair in s.split(',') {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("invalid weight '{pair}'"))?;
+            let value = value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format!("invalid value of weight '{pair}'"))?;
+
+            match name.trim() {
+                "height" => weights.height = value,
+                "holes" => weights.holes = value,
+                "bumpiness" => weights.bumpiness = value,
+                "wells" => weights.wells = value,
+                "lines" => weights.lines = value,
+                _ => return Err(format!("unknown weight '{name}'")),
+            }
+        }
+
+        Ok(weights)
+    }
+}
+
+impl Weights {
+    /// Scores the board left behind by a placement, which cleared the given
+    /// number of lines.
+    pub fn evaluate(&self, board: &Board, lines: usize) -> f64 {
+        let height = board.get_column_heights().iter().sum::<usize>();
+        let wells = board.get_well_depths().iter().sum::<usize>();
+
+        self.height * height as f64
+            + self.holes * board.count_holes() as f64
+            + self.bumpiness * board.get_bumpiness() as f64
+            + self.wells * wells as f64
+            + self.lines * lines as f64
+    }
+}
+
+/// Plays the game by itself: for every new piece it searches all the
+/// placements, picks the best one according to its weights, looking one piece
+/// ahead, and then plays the inputs leading there. The placements can also be
+/// picked by an external bot instead.
+pub struct Bot {
+    external: Option<tbp::Client>,
+    input_interval: Duration,
+    last_input: Instant,
+    level: BotLevel,
+    plan: Option<VecDeque<Input>>,
+    weights: Weights,
+}
+
+impl Bot {
+    pub fn new(level: BotLevel, weights: Weights) -> Self {
+        Self {
+            external: None,
+            input_interval: Duration::ZERO,
+            last_input: Instant::now(),
+            level,
+            plan: None,
+            weights,
+        }
+    }
+
+    /// Holds the next input back, as the game was paused for this long.
+    pub fn postpone(&mut self, duration: Duration) {
+        self.last_input += duration;
+    }
+
+    /// Plays the placements picked by an external bot, at the pace of the
+    /// given level.
+    pub fn external(level: BotLevel, client: tbp::Client) -> Self {
+        Self {
+            external: Some(client),
+            ..Self::new(level, Weights::default())
+        }
+    }
+
+    pub fn get_level(&self) -> BotLevel {
+        self.level
+    }
+
+    /// Returns the name of the external bot, if there is one.
+    pub fn get_external_name(&self) -> Option<&str> {
+        self.external.as_ref().map(|client| client.get_name())
+    }
+
+    /// Forgets the plan of the previous piece.
+    pub fn piece_spawned(&mut self, board: &Board, next_piece: Option<&Piece>, attack: &Attack) {
+        self.plan = None;
+        if let Some(client) = self.external.as_mut() {
+            client.piece_spawned(board, next_piece, attack);
+        }
+    }
+
+    /// Returns when the next input of the plan is due, if there is one. The
+    /// soft drops, held down, are due right away.
+    pub fn get_next_input_at(&self) -> Option<Instant> {
+        match self.plan.as_ref()?.front()? {
+            Input::SoftDrop => Some(Instant::now()),
+            _ => Some(self.last_input + self.input_interval),
+        }
+    }
+
+    /// Returns the next input to play, once it's time to play it. The first
+    /// call for a new piece decides where the piece goes.
+    pub fn next_input(&mut self, board: &Board, next_piece: Option<&Piece>) -> Option<Input> {
+        if !board.has_piece() {
+            return None;
+        }
+
+        let plan = match self.plan.as_mut() {
+            Some(plan) => plan,
+            None => {
+                let inputs = match self.external.as_mut() {
+                    // waiting for the suggestion of the external bot
+                    Some(client) => client.get_inputs(board)?,
+                    None => self
+                        .find_best(board, next_piece.map(|p| p.get_tetromino()))
+                        .map(|placement| placement.inputs)
+                        .unwrap_or_default(),
+                };
+                let mut plan = VecDeque::from(inputs);
+                plan.push_back(Input::HardDrop);
+
+                // the inputs are spread evenly over the time of each piece, the
+                // soft drops being held down in between
+                let piece_time = Duration::from_secs_f64(1.0 / self.level.get_pieces_per_second());
+                let presses = plan.iter().filter(|input| **input != Input::SoftDrop);
+                self.input_interval = piece_time / presses.count() as u32;
+                self.plan.insert(plan)
+            }
+        };
+
+        // the soft drops of the plan are held down, and skipped once gravity
+        // took the piece to the ground already, so as not to lock it
+        while plan.front() == Some(&Input::SoftDrop) {
+            plan.pop_front();
+            if !board.is_piece_grounded() {
+                return Some(Input::SoftDrop);
+            }
+        }
+
+        if self.last_input.elapsed() < self.input_interval {
+            return None;
+        }
+        self.last_input = Instant::now();
+
+        plan.pop_front()
+    }
+
+    /// Finds the placement of the active piece leaving the best board behind,
+    /// given the best placement of the next piece on that board.
+    pub fn find_best(&self, board: &Board, next: Option<&Tetromino>) -> Option<Placement> {
+        let piece = board.get_piece()?;
+        board
+            .legal_placements(piece)
+            .into_iter()
+            .filter_map(|placement| {
+                let (after, lines) = play(board, &placement)?;
+                let score = match next {
+                    Some(tetromino) => {
+                        self.best_score(&after, tetromino)? + self.weights.lines * lines as f64
+                    }
+                    None => self.weights.evaluate(&after, lines),
+                };
+                Some((placement, score))
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(placement, _)| placement)
+    }
+
+    /// Returns the score of the best placement of a piece, or none when the
+    /// piece doesn't even fit on the board.
+    fn best_score(&self, board: &Board, tetromino: &Tetromino) -> Option<f64> {
+        let piece = Piece::new(tetromino.clone(), tetromino.get_spawn_position());
+        let mut board = board.clone();
+        if !board.add_piece(piece.clone()) {
+            return None;
+        }
+
+        board
+            .legal_placements(&piece)
+            .iter()
+            .filter_map(|placement| play(&board, placement))
+            .map(|(after, lines)| self.weights.evaluate(&after, lines))
+            .max_by(|a, b| a.total_cmp(b))
+    }
+}
+
+/// Plays the inputs of a placement, returning the board once the piece locked
+/// along with the number of lines it cleared.
+pub fn play(board: &Board, placement: &Placement) -> Option<(Board, usize)> {
+    let mut board = board.clone();
+    for input in &placement.inputs {
+        if *input == Input::SoftDrop {
+            board.drop_piece();
+        } else {
+            board.apply_input(*input);
+        }
+    }
+
+    let score = board.land_piece();
+    if board.get_last_lock().cells != placement.cells {
+        return None;
+    }
+
+    Some((board, score.lines_destroyed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_weights() {
+        let weights = "holes=-1, lines=2".parse::<Weights>().unwrap();
+
+        assert_eq!(-1.0, weights.holes);
+        assert_eq!(2.0, weights.lines);
+        assert_eq!(Weights::default().height, weights.height);
+        assert!("holes".parse::<Weights>().is_err());
+        assert!("depth=1".parse::<Weights>().is_err());
+    }
+
+    #[test]
+    fn takes_the_tetris() {
+        let mut board = Board::from_text("XXXXXXXXX.\nXXXXXXXXX.\nXXXXXXXXX.\nXXXXXXXXX.").unwrap();
+        board.add_piece(Piece::new(Tetromino::I, Tetromino::I.get_spawn_position()));
+
+        let bot = Bot::new(BotLevel::Max, Weights::default());
+        let placement = bot.find_best(&board, Some(&Tetromino::O)).unwrap();
+        let (after, lines) = play(&board, &placement).unwrap();
+
+        assert_eq!(4, lines);
+        assert!(after.is_empty());
+    }
+
+    #[test]
+    fn plays_a_whole_piece() {
+        let mut board = Board::new();
+        board.add_piece(Piece::new(Tetromino::T, Tetromino::T.get_spawn_position()));
+
+        let mut bot = Bot::new(BotLevel::Max, Weights::default());
+        let started = Instant::now();
+        while board.has_piece() && started.elapsed() < Duration::from_secs(5) {
+            if let Some(input) = bot.next_input(&board, None) {
+                board.apply_input(input);
+            }
+        }
+
+        // a flat T on the floor, no holes
+        assert!(!board.has_piece());
+        assert_eq!(0, board.count_holes());
+        assert_eq!(2, board.get_stack_height());
+    }
+}
@@ -1,25 +1,44 @@
 use std::{
-    io::{Stdout, Write},
-    time::Duration,
+    collections::VecDeque,
+    time::{Duration, Instant},
 };
 
-use crossterm::{
-    cursor::{Hide, MoveTo, MoveToNextLine, Show},
-    terminal::{disable_raw_mode, enable_raw_mode, Clear},
-    ExecutableCommand, QueueableCommand,
-};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use kira::{
     sound::static_sound::{StaticSoundData, StaticSoundHandle},
     AudioManager, AudioManagerSettings, Decibels, DefaultBackend, Semitones, Tween,
 };
-use rand::{rngs::ThreadRng, Rng};
+use rand::rngs::ThreadRng;
 
 use crate::{
-    pieces::{Piece, Tetromino},
-    utils::Score,
+    animation::Animations,
+    board::Board,
+    bot::Bot,
+    finesse::Finesse,
+    hint::{self, Hint},
+    mode::{
+        self, GameMode, MASTER_LEVEL_MAX, MASTER_SECTION_LEVELS, SURVIVAL_RISE_ACCELERATION,
+        SURVIVAL_RISE_INTERVAL, SURVIVAL_RISE_INTERVAL_MIN,
+    },
+    narration,
+    pause::{Item, PauseMenu},
+    pieces::{Cell, Piece},
+    player::Player,
+    puzzle::{self, Challenge},
+    render::{
+        self, Line, Renderer, Style, TerminalRenderer, Tile, View, PANEL_COLUMN, PREVIEW_COLUMN,
+        STATS_COLUMN,
+    },
+    stats::Stats,
+    terminal::{self, Signals},
+    theme::Theme,
+    training::Training,
+    utils::{self, Direction, Input, LockResult, Score},
 };
 
-const LEVEL_INC_LINES: u32 = 5;
+/// The screen is drawn at most this many times per second.
+const RENDER_RATE_MAX: u32 = 60;
+
 const MUSIC_INC_LEVEL: u32 = 6;
 const MUSIC_INC_SPEED: u64 = 5;
 
@@ -29,47 +48,100 @@ const VOLUME_INC_STEP: f32 = 1.00;
 
 const SONGS_COUNT: usize = 3;
 
-const ASSET_PATH: &str = "res";
+const WARNING_METER_SIZE: u32 = 5;
+
+/// Width of the hint explanation lines, for them to stay within the panel.
+const HINT_WIDTH: usize = 24;
+
+/// Longest bar of the pieces histogram, in cells.
+const STATS_BAR_MAX: usize = 6;
+
+/// Placements the zen mode remembers, the oldest ones can't be undone.
+const HISTORY_MAX: usize = 100;
+
+pub const ASSET_PATH: &str = "res";
+
+/// State of the game right after a piece entered the board, allowing the zen
+/// mode to undo placements.
+struct Snapshot {
+    board: Board,
+    player: Player,
+}
 
 pub struct Context {
+    animations: Animations,
     audio_manager: Option<AudioManager>,
-    level: u32,
+    bot: Option<Bot>,
+    challenge: Option<Challenge>,
+    combo: u64,
+    finesse: Option<Finesse>,
+    finished_in: Option<Duration>,
+    garbage_rise_interval: Duration,
+    garbage_rise_timer: Instant,
+    garbage_rows: usize,
+    gravity_enabled: bool,
+    hint: Option<Hint>,
+    hints_enabled: bool,
+    history: VecDeque<Snapshot>,
+    mode: GameMode,
     muted: bool,
-    next_piece: Option<Piece>,
-    random_bag: Vec<Tetromino>,
+    pause_menu: Option<PauseMenu>,
+    player: Player,
+    rendered: Option<Instant>,
+    renderer: Box<dyn Renderer>,
     rng: ThreadRng,
-    score: Score,
     song_handle: Option<StaticSoundHandle>,
     song_index: usize,
     songs: Vec<StaticSoundData>,
-    stdout: Stdout,
+    signals: Signals,
+    started: Instant,
+    stats: Stats,
+    stats_shown: bool,
+    terminal: Option<terminal::Guard>,
+    training: Option<Training>,
     volume: f32,
 }
 
 impl Context {
-    pub fn new() -> Self {
+    pub fn new(mode: GameMode) -> Self {
         Self {
+            animations: Animations::default(),
             audio_manager: None,
-            level: 0,
+            bot: None,
+            challenge: None,
+            combo: 1,
+            finesse: (mode == GameMode::Drill).then(|| Finesse::new(true)),
+            finished_in: None,
+            garbage_rise_interval: SURVIVAL_RISE_INTERVAL,
+            garbage_rise_timer: Instant::now(),
+            garbage_rows: 0,
+            gravity_enabled: true,
+            hint: None,
+            hints_enabled: false,
+            history: VecDeque::new(),
+            mode,
             muted: false,
-            next_piece: None,
-            random_bag: Vec::new(),
+            pause_menu: None,
+            player: Player::new(),
+            rendered: None,
+            renderer: Box::new(TerminalRenderer::new(Style::default(), Theme::default())),
             rng: rand::rng(),
-            score: Score::default(),
             song_handle: None,
             song_index: 0,
             songs: Vec::new(),
-            stdout: std::io::stdout(),
+            signals: Signals::default(),
+            started: Instant::now(),
+            stats: Stats::default(),
+            stats_shown: false,
+            terminal: None,
+            training: None,
             volume: 1.0,
         }
     }
 
     pub fn setup(&mut self) -> std::io::Result<()> {
-        enable_raw_mode()?;
-
-        self.stdout
-            .execute(Clear(crossterm::terminal::ClearType::All))?
-            .execute(Hide)?;
+        self.terminal = Some(terminal::Guard::enter(self.renderer.is_linear())?);
+        self.signals = Signals::register()?;
 
         if self.load_songs() && let Ok(manager) = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default())
         {
@@ -81,99 +153,635 @@ impl Context {
     }
 
     pub fn teardown(&mut self) -> std::io::Result<()> {
-        self.stdout.execute(Show)?;
+        match self.terminal.take() {
+            Some(guard) => guard.leave(),
+            None => Ok(()),
+        }
+    }
+
+    /// Suspends the game as Ctrl+Z does, and draws it again once resumed.
+    /// The game is paused first, so that its clocks skip the time stopped.
+    pub fn suspend(&mut self) -> std::io::Result<()> {
+        self.pause();
+        if let Some(guard) = self.terminal.as_ref() {
+            guard.suspend()?;
+            let (width, height) = crossterm::terminal::size()?;
+            self.resize(width, height);
+        }
+
+        Ok(())
+    }
+
+    /// Suspends the game when asked to by a signal, and tells whether one
+    /// asked to end it.
+    pub fn handle_signals(&mut self) -> std::io::Result<bool> {
+        if self.signals.take_suspend() {
+            self.suspend()?;
+        }
+
+        Ok(self.signals.take_terminate())
+    }
+
+    /// Draws the board along with the side panel. While paused, the menu
+    /// hides the board and the next piece.
+    pub fn print_game(&mut self, board: &Board) -> std::io::Result<()> {
+        let view = self.game_view(board);
+        self.render(&view)
+    }
+
+    /// Animates what happened on the board since the last call, and counts
+    /// it in the statistics.
+    pub fn observe(&mut self, board: &mut Board) {
+        for event in board.take_events() {
+            self.renderer.observe(&event);
+            self.stats.observe(&event);
+            self.animations.start(event);
+        }
+    }
+
+    /// Greys the stack out, as the game is over.
+    pub fn top_out(&mut self, board: &mut Board) {
+        self.observe(board);
+        self.renderer.announce("game over");
+        self.animations.top_out();
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.animations.is_running()
+    }
+
+    pub fn render(&mut self, view: &View) -> std::io::Result<()> {
+        self.rendered = Some(Instant::now());
+        self.renderer.render(view)
+    }
+
+    /// Tells whether it's time to draw the screen again, keeping the frame
+    /// rate under `RENDER_RATE_MAX`.
+    pub fn is_render_due(&self) -> bool {
+        self.rendered
+            .is_none_or(|rendered| rendered.elapsed() >= Duration::from_secs(1) / RENDER_RATE_MAX)
+    }
+
+    /// Returns how long the game loop may wait for the keys, until the given
+    /// deadline at most, the next drawing of the screen or the next input of
+    /// the computer.
+    pub fn get_idle_time(&self, deadline: Instant) -> Duration {
+        let render_at = self.rendered.map_or_else(Instant::now, |rendered| {
+            rendered + Duration::from_secs(1) / RENDER_RATE_MAX
+        });
+        let bot_at = self.bot.as_ref().and_then(Bot::get_next_input_at);
+
+        bot_at
+            .map_or(deadline, |bot_at| bot_at.min(deadline))
+            .min(render_at)
+            .saturating_duration_since(Instant::now())
+    }
+
+    /// Lays the screen out again once the terminal got resized.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.renderer.resize(width, height);
+    }
+
+    /// Tells whether the terminal is too small to show the game, which
+    /// pauses it.
+    pub fn is_too_small(&self) -> bool {
+        self.renderer.is_too_small()
+    }
+
+    pub fn set_renderer(&mut self, renderer: Box<dyn Renderer>) {
+        self.renderer = renderer;
+    }
+
+    /// Returns the piece to play next, or none when a challenge ran out of pieces.
+    pub fn get_piece(&mut self) -> Option<Piece> {
+        self.player.get_piece()
+    }
+
+    /// Replaces the random bag with the fixed piece sequence of the challenge,
+    /// and returns the board the challenge starts from.
+    pub fn start_challenge(&mut self, challenge: Challenge) -> Board {
+        let board = challenge.board.clone();
+
+        // challenges play a fixed sequence of pieces, which is never refilled
+        self.player.random_bag = challenge.pieces.iter().rev().cloned().collect();
+        self.player.next_piece = None;
+        self.player.refill = false;
+        self.challenge = Some(challenge);
+
+        board
+    }
+
+    /// Starts the perfect clear training, and returns the board of the first attempt.
+    pub fn start_training(&mut self, training: Training) -> Board {
+        let board = training.get_board();
+
+        self.player.fixed_bag = training.get_bag().cloned();
+        self.player.clear_queue();
+        self.training = Some(training);
+
+        board
+    }
+
+    /// Checks whether the piece that was just locked ended the current perfect
+    /// clear attempt, in which case the board and the queue start over.
+    pub fn check_attempt(&mut self, board: &mut Board) {
+        if let Some(training) = self.training.as_mut()
+            && training.record_lock(board).is_some()
+        {
+            *board = training.get_board();
+            self.player.clear_queue();
+        }
+    }
+
+    /// Ends the current perfect clear attempt as failed once the next piece
+    /// can't enter the board, and returns the board of the next attempt.
+    pub fn fail_attempt(&mut self) -> Option<Board> {
+        let training = self.training.as_mut()?;
+        training.record_top_out();
+        self.player.clear_queue();
+
+        Some(training.get_board())
+    }
+
+    /// Hands the pieces over to the computer.
+    pub fn start_bot(&mut self, bot: Bot) {
+        self.bot = Some(bot);
+    }
+
+    pub fn has_bot(&self) -> bool {
+        self.bot.is_some()
+    }
+
+    /// Returns the input the computer plays right now, if any.
+    pub fn bot_input(&mut self, board: &Board) -> Option<Input> {
+        self.bot
+            .as_mut()?
+            .next_input(board, self.player.next_piece.as_ref())
+    }
+
+    /// Counts the finesse faults from now on, the drill mode always does.
+    pub fn enable_finesse(&mut self) {
+        if self.finesse.is_none() {
+            self.finesse = Some(Finesse::new(false));
+        }
+    }
+
+    /// Notifies that the player pressed a key acting on the active piece.
+    pub fn record_input(&mut self, input: Input) {
+        self.stats.record_input(input);
+        if let Some(finesse) = self.finesse.as_mut() {
+            finesse.record_input(input);
+        }
+    }
+
+    /// Notifies that the bot played an input, which is judged for finesse
+    /// but not counted in the keys of the player.
+    pub fn record_bot_input(&mut self, input: Input) {
+        if let Some(finesse) = self.finesse.as_mut() {
+            finesse.record_input(input);
+        }
+    }
+
+    /// Judges the finesse of the piece that was just locked. Drills always
+    /// continue on an empty board.
+    pub fn check_finesse(&mut self, board: &mut Board) {
+        if let Some(finesse) = self.finesse.as_mut() {
+            finesse.piece_locked(board.get_last_lock());
+            if finesse.is_drill() {
+                *board = Board::new();
+            }
+        }
+    }
 
-        disable_raw_mode()
+    /// Checks whether the piece that was just locked completed the challenge.
+    pub fn check_goal(&mut self, lock: &LockResult) {
+        if let Some(challenge) = self.challenge.as_ref()
+            && self.finished_in.is_none()
+            && challenge
+                .goal
+                .is_reached(self.player.score.lines_destroyed, lock)
+        {
+            puzzle::save_progress(challenge.number + 1);
+            self.finished_in = Some(self.get_play_time());
+        }
     }
 
-    #[allow(non_contiguous_range_endpoints)]
-    pub fn print_game(&mut self, board: String) -> std::io::Result<()> {
-        let next_piece = if let Some(p) = self.next_piece.clone() {
-            p.to_string()
+    /// Accounts for the score of a piece that was just locked on the board.
+    pub fn increment_score(&mut self, score: Score) {
+        let prev_level = self.player.level;
+
+        if let GameMode::Master = self.mode {
+            self.increment_master_score(score);
         } else {
-            String::new()
+            self.player.increment_score(score);
+        }
+
+        if self.get_music_stage(self.player.level) > self.get_music_stage(prev_level) {
+            let stage = self.get_music_stage(self.player.level);
+            if stage.is_multiple_of(MUSIC_INC_LEVEL) {
+                self.change_song();
+            } else {
+                self.update_playback_rate(stage % MUSIC_INC_LEVEL + 1);
+            }
+        }
+    }
+
+    /// Notifies that a new piece entered the board. In master mode, this
+    /// advances the level, unless the level is at the end of a section. In zen
+    /// mode, the board is remembered so that the placement can be undone.
+    pub fn piece_spawned(&mut self, board: &mut Board) {
+        self.observe(board);
+        if let Some(finesse) = self.finesse.as_mut() {
+            finesse.piece_spawned(board, &mut self.rng);
+        }
+        if let Some(bot) = self.bot.as_mut() {
+            let attack = self.stats.get_attack();
+            bot.piece_spawned(board, self.player.next_piece.as_ref(), attack);
+        }
+        if self.hints_enabled {
+            self.update_hint(board);
+        }
+
+        match self.mode {
+            GameMode::Master
+                if self.player.level < mode::master_section_end(self.player.level)
+                    && self.player.level + 1 < MASTER_LEVEL_MAX =>
+            {
+                self.player.level += 1;
+            }
+            GameMode::Zen => {
+                self.history.push_back(self.snapshot(board));
+                if self.history.len() > HISTORY_MAX {
+                    self.history.pop_front();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Takes back the last placement, restoring the board and the queue to the
+    /// moment the previous piece entered the board.
+    pub fn undo(&mut self, board: &mut Board) -> bool {
+        if self.history.len() < 2 {
+            return false;
+        }
+
+        self.history.pop_back();
+        if let Some(snapshot) = self.history.back() {
+            *board = snapshot.board.clone();
+            self.player = snapshot.player.clone();
+        }
+
+        true
+    }
+
+    /// Exchanges the active piece with the next one, in zen mode only.
+    pub fn swap_next_piece(&mut self, board: &mut Board) -> bool {
+        if self.mode != GameMode::Zen {
+            return false;
+        }
+
+        if let Some(next) = self.player.next_piece.clone()
+            && let Some(current) = board.take_piece()
+        {
+            if board.add_piece(next) {
+                let tetromino = current.get_tetromino().clone();
+                let position = tetromino.get_spawn_position();
+                self.player.next_piece = Some(Piece::new(tetromino, position));
+                if self.hints_enabled {
+                    self.update_hint(board);
+                }
+
+                self.history.pop_back();
+                self.history.push_back(self.snapshot(board));
+                return true;
+            }
+
+            // the next piece doesn't fit, so keep playing the current one
+            board.add_piece(current);
+        }
+
+        false
+    }
+
+    /// Switches the hints on and off, except in the finesse drill which has
+    /// targets of its own, and when the computer is playing.
+    pub fn hint_toggle(&mut self, board: &mut Board) {
+        if self.mode == GameMode::Drill || self.bot.is_some() {
+            return;
+        }
+
+        self.hints_enabled = !self.hints_enabled;
+        if self.hints_enabled {
+            self.update_hint(board);
+        } else {
+            self.hint = None;
+            board.set_target(Vec::new());
+        }
+    }
+
+    /// Pauses the game, stopping its timers and the music, and opens the
+    /// pause menu.
+    pub fn pause(&mut self) {
+        if self.pause_menu.is_some() {
+            return;
+        }
+
+        self.pause_menu = Some(PauseMenu::new());
+        self.renderer.announce("paused");
+        self.announce_menu();
+        if let Some(song) = self.song_handle.as_mut()
+            && !self.muted
+        {
+            song.pause(Tween::default());
+        }
+    }
+
+    /// Closes the pause menu, and lets the timers go on from where they were
+    /// stopped.
+    pub fn resume(&mut self) {
+        let Some(menu) = self.pause_menu.take() else {
+            return;
         };
-        let next_piece_lines = next_piece.lines().collect::<Vec<&str>>();
 
-        self.stdout.queue(MoveTo(0, 0))?;
+        let paused_for = menu.get_duration();
+        self.renderer.announce("resumed");
+        self.started += paused_for;
+        self.garbage_rise_timer += paused_for;
+        if let Some(bot) = self.bot.as_mut() {
+            bot.postpone(paused_for);
+        }
+        if let Some(song) = self.song_handle.as_mut()
+            && !self.muted
+        {
+            song.resume(Tween::default());
+        }
+    }
 
-        for (i, line) in board.lines().enumerate() {
-            write!(self.stdout, "{line}")?;
+    /// Tells the selected item of the pause menu, for the players who don't
+    /// see it.
+    pub fn announce_menu(&mut self) {
+        if let Some(line) = self
+            .get_menu_lines()
+            .iter()
+            .find(|line| line.starts_with('>'))
+        {
+            let item = line.trim_start_matches('>').trim().to_lowercase();
+            self.renderer.announce(&item);
+        }
+    }
 
-            match i {
-                0 => {
-                    let nl = if let Some(next_piece_line) = next_piece_lines.get(i) {
-                        *next_piece_line
-                    } else {
-                        ""
-                    };
-                    write!(self.stdout, "     NEXT PIECE:    {nl}")?;
+    /// Tells the height of every column.
+    pub fn announce_heights(&mut self, board: &Board) {
+        let heights = board.get_column_heights();
+        self.renderer
+            .announce(&narration::describe_heights(&heights));
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause_menu.is_some()
+    }
+
+    /// Returns how long the game has been paused, if it is, the time the
+    /// process was suspended included.
+    pub fn get_pause_duration(&self) -> Option<Duration> {
+        self.pause_menu.as_ref().map(PauseMenu::get_duration)
+    }
+
+    /// Returns the lines of the pause menu, drawn in place of the well.
+    pub fn get_menu_lines(&self) -> Vec<String> {
+        self.pause_menu
+            .as_ref()
+            .map(|menu| {
+                menu.get_lines(|item| match item {
+                    Item::Stats => Some(self.stats_shown),
+                    Item::Hints => Some(self.hints_enabled),
+                    Item::Music => Some(!self.muted),
+                    _ => None,
+                })
+            })
+            .unwrap_or_default()
+    }
+
+    /// Browses the pause menu, and returns the item picked for the game loop
+    /// to carry out, if any.
+    pub fn pause_menu_event(
+        &mut self,
+        board: &mut Board,
+        event: Event,
+    ) -> std::io::Result<Option<Item>> {
+        match event {
+            Event::Resize(width, height) => self.resize(width, height),
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('z'),
+                modifiers: KeyModifiers::CONTROL,
+                kind: KeyEventKind::Press,
+                ..
+            }) => self.suspend()?,
+            Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }) => match code {
+                KeyCode::Up => {
+                    if let Some(menu) = self.pause_menu.as_mut() {
+                        menu.up();
+                    }
+                    self.announce_menu();
                 }
-                1..4 => {
-                    let nl = if let Some(next_piece_line) = next_piece_lines.get(i) {
-                        *next_piece_line
+                KeyCode::Down => {
+                    if let Some(menu) = self.pause_menu.as_mut() {
+                        menu.down();
+                    }
+                    self.announce_menu();
+                }
+                KeyCode::Enter => return Ok(self.pick_menu_item(board)),
+                // Esc leaves the options, and then the menu
+                KeyCode::Esc => {
+                    if self.pause_menu.as_mut().is_some_and(PauseMenu::back) {
+                        self.announce_menu();
                     } else {
-                        ""
-                    };
-
-                    write!(self.stdout, "                    {nl}")?
+                        self.resume();
+                    }
                 }
-                5 => write!(self.stdout, "     MOVE LEFT:     ⬅️")?,
-                6 => write!(self.stdout, "     MOVE RIGHT:    ➡️")?,
-                7 => write!(self.stdout, "     DROP SOFT:     ⬇️")?,
-                9 => write!(self.stdout, "     ROTATE LEFT:   Z")?,
-                10 => write!(self.stdout, "     ROTATE RIGHT:  X")?,
-                11 => write!(self.stdout, "     HOLD:          C")?,
-                12 => write!(self.stdout, "     DROP HARD:     SPACEBAR")?,
-                14 => write!(self.stdout, "     VOLUME:        + / -")?,
-                15 => write!(self.stdout, "     MUTE TOGGLE:   M")?,
-                17 => write!(self.stdout, "     LEVEL:         {}", self.level + 1)?,
-                18 => write!(
-                    self.stdout,
-                    "     LINES:         {}",
-                    self.score.lines_destroyed
-                )?,
-                19 => write!(self.stdout, "     POINTS:        {}", self.score.points)?,
+                KeyCode::Char('c' | 'C') => self.resume(),
                 _ => {}
+            },
+            _ => {}
+        }
+
+        Ok(None)
+    }
+
+    /// Carries out the item picked in the pause menu, returning those the
+    /// game loop has to: restarting and quitting.
+    pub fn pick_menu_item(&mut self, board: &mut Board) -> Option<Item> {
+        match self.pause_menu.as_mut()?.pick() {
+            Some(Item::Resume) => {
+                self.resume();
+                return None;
             }
-            self.stdout.queue(MoveToNextLine(1))?;
+            Some(Item::Stats) => self.stats_toggle(),
+            Some(Item::Hints) => self.hint_toggle(board),
+            // the music stays paused until the game resumes
+            Some(Item::Music) => self.muted = !self.muted,
+            Some(item) => return Some(item),
+            // the options page opened or closed
+            None => {}
         }
+        self.announce_menu();
 
-        self.stdout.queue(MoveToNextLine(1))?;
-        self.stdout.flush()
+        None
     }
 
-    pub fn get_piece(&mut self) -> Piece {
-        let piece = if let Some(p) = self.next_piece.clone() {
-            p
+    /// Starts the game over, and returns its board. The terminal, the sounds,
+    /// the computer and the settings are kept.
+    pub fn restart(&mut self) -> Board {
+        let mut fresh = Self::new(self.mode);
+        std::mem::swap(&mut fresh.audio_manager, &mut self.audio_manager);
+        std::mem::swap(&mut fresh.renderer, &mut self.renderer);
+        std::mem::swap(&mut fresh.signals, &mut self.signals);
+        std::mem::swap(&mut fresh.song_handle, &mut self.song_handle);
+        std::mem::swap(&mut fresh.songs, &mut self.songs);
+        std::mem::swap(&mut fresh.terminal, &mut self.terminal);
+        fresh.bot = self.bot.take();
+        fresh.finesse = self
+            .finesse
+            .as_ref()
+            .map(|finesse| Finesse::new(finesse.is_drill()));
+        fresh.hints_enabled = self.hints_enabled;
+        fresh.muted = self.muted;
+        fresh.song_index = self.song_index;
+        fresh.stats_shown = self.stats_shown;
+        fresh.volume = self.volume;
+
+        let board = if let Some(challenge) = self.challenge.take() {
+            fresh.start_challenge(challenge)
+        } else if let Some(training) = self.training.take() {
+            fresh.start_training(training)
         } else {
-            self.take_from_random_bag()
+            Board::new()
         };
+        *self = fresh;
 
-        self.next_piece = Some(self.take_from_random_bag());
+        if let Some(song) = self.song_handle.as_mut()
+            && !self.muted
+        {
+            song.resume(Tween::default());
+        }
+        self.update_playback_rate(0);
 
-        piece
+        board
     }
 
-    pub fn increment_score(&mut self, score: Score) {
-        let prev_level = self.level;
+    pub fn stats_toggle(&mut self) {
+        self.stats_shown = !self.stats_shown;
+    }
 
-        self.score.increment(score);
-        self.level = self.score.lines_destroyed as u32 / LEVEL_INC_LINES;
+    pub fn gravity_toggle(&mut self) {
+        if self.mode == GameMode::Zen {
+            self.gravity_enabled = !self.gravity_enabled;
+        }
+    }
 
-        if self.level > prev_level {
-            if self.level.is_multiple_of(MUSIC_INC_LEVEL) {
-                self.change_song();
-            } else {
-                self.update_playback_rate(self.level % MUSIC_INC_LEVEL + 1);
-            }
+    pub fn get_mode(&self) -> GameMode {
+        self.mode
+    }
+
+    /// Records how many garbage rows are still on the board. In dig mode,
+    /// clearing the last one finishes the game.
+    pub fn update_garbage_rows(&mut self, garbage_rows: usize) {
+        self.garbage_rows = garbage_rows;
+
+        if let GameMode::Dig { .. } = self.mode
+            && garbage_rows == 0
+            && self.finished_in.is_none()
+        {
+            self.finished_in = Some(self.get_play_time());
         }
     }
 
-    pub fn get_game_speed_micros(&self) -> f64 {
-        let float_level = self.level as f64;
+    /// Returns how many garbage rows must rise from the bottom right now. In
+    /// survival mode a row rises on a timer, which accelerates after every rise.
+    pub fn take_rising_garbage(&mut self) -> usize {
+        if let GameMode::Survival = self.mode
+            && self.garbage_rise_timer.elapsed() >= self.garbage_rise_interval
+        {
+            self.garbage_rise_timer = Instant::now();
+            self.garbage_rise_interval = SURVIVAL_RISE_INTERVAL_MIN
+                .max(self.garbage_rise_interval.mul_f64(SURVIVAL_RISE_ACCELERATION));
+            1
+        } else {
+            0
+        }
+    }
 
-        (0.8 - (float_level * 0.007)).powf(float_level)
+    pub fn is_finished(&self) -> bool {
+        self.finished_in.is_some()
+    }
+
+    pub fn get_summary(&self) -> Option<String> {
+        match self.mode {
+            GameMode::Dig { rows, .. } => self.finished_in.map(|elapsed| {
+                format!(
+                    "Dig cleared: {rows} garbage rows in {}",
+                    utils::format_duration(elapsed)
+                )
+            }),
+            GameMode::Survival => Some(format!(
+                "Survived for {}",
+                utils::format_duration(self.get_play_time())
+            )),
+            GameMode::Master => Some(format!(
+                "Master: grade {} at level {} in {}",
+                mode::master_grade(self.player.score.points),
+                self.player.level,
+                utils::format_duration(self.finished_in.unwrap_or_else(|| self.get_play_time()))
+            )),
+            GameMode::Puzzle { .. } => self.challenge.as_ref().map(|challenge| {
+                let number = challenge.number;
+                if self.finished_in.is_none() {
+                    format!(
+                        "Challenge {number} failed, try again with: textris --mode puzzle --challenge {number}"
+                    )
+                } else if number < puzzle::count_challenges() {
+                    format!("Challenge {number} complete, challenge {} is unlocked!", number + 1)
+                } else {
+                    format!("Challenge {number} complete, you solved all the challenges!")
+                }
+            }),
+            GameMode::PerfectClear => self.training.as_ref().map(|training| {
+                format!(
+                    "Perfect clear training ({}): {} of {} attempts succeeded ({:.0}%)",
+                    training.get_name(),
+                    training.get_successes(),
+                    training.get_attempts(),
+                    training.get_success_rate()
+                )
+            }),
+            GameMode::Drill => self.finesse.as_ref().map(|finesse| {
+                format!(
+                    "Finesse drill: {} of {} targets hit, {} finesse faults",
+                    finesse.get_hits(),
+                    finesse.get_pieces(),
+                    finesse.get_faults()
+                )
+            }),
+            // the versus matches sum up their own results
+            GameMode::Marathon | GameMode::Zen | GameMode::Versus { .. } | GameMode::Demo => None,
+        }
+    }
+
+    /// Returns the gravity, in rows per frame.
+    pub fn get_gravity(&self) -> f64 {
+        // drill pieces stay in place until they are dropped
+        if !self.gravity_enabled || self.mode == GameMode::Drill {
+            0.0
+        } else if let GameMode::Master = self.mode {
+            mode::master_gravity(self.player.level)
+        } else {
+            self.player.get_gravity()
+        }
     }
 
     pub fn mute_toggle(&mut self) {
@@ -198,34 +806,292 @@ impl Context {
         self.update_volume();
     }
 
-    fn take_from_random_bag(&mut self) -> Piece {
-        if self.random_bag.is_empty() {
-            self.refill_random_bag();
+    fn side_panel(&self) -> Vec<Line> {
+        // the next piece itself is drawn right of its label, by `print_game`
+        let mut panel = Vec::new();
+        let mut meter_row = None;
+        for i in 0..20 {
+            let line = match i {
+                0 => String::from("NEXT PIECE:"),
+                5 => String::from("MOVE LEFT:     "),
+                6 => String::from("MOVE RIGHT:    "),
+                7 => String::from("DROP SOFT:     "),
+                9 => String::from("ROTATE LEFT:   Z"),
+                10 => String::from("ROTATE RIGHT:  X"),
+                11 => String::from("HOLD:          C"),
+                12 => String::from("DROP HARD:     SPACEBAR"),
+                14 => String::from("VOLUME:        + / -"),
+                15 => String::from("MUTE TOGGLE:   M"),
+                17 => format!("LEVEL:         {}", self.player.level + 1),
+                18 => format!("LINES:         {}", self.player.score.lines_destroyed),
+                19 => format!("POINTS:        {}", self.player.score.points),
+                _ => String::new(),
+            };
+            panel.push(line);
+        }
+
+        match self.mode {
+            GameMode::Dig { .. } => {
+                let elapsed = self.finished_in.unwrap_or_else(|| self.get_play_time());
+
+                panel.push(format!("MODE:          {}", self.mode.get_name()));
+                panel.push(format!("GARBAGE LEFT:  {}", self.garbage_rows));
+                panel.push(format!("TIME:          {}", utils::format_duration(elapsed)));
+            }
+            GameMode::Survival => {
+                panel.push(format!("MODE:          {}", self.mode.get_name()));
+                meter_row = Some(panel.len());
+                panel.push(String::from("INCOMING:      "));
+                panel.push(format!(
+                    "TIME:          {}",
+                    utils::format_duration(self.get_play_time())
+                ));
+            }
+            GameMode::Master => {
+                let elapsed = self.finished_in.unwrap_or_else(|| self.get_play_time());
+
+                panel[17] = format!(
+                    "LEVEL:         {} / {}",
+                    self.player.level,
+                    mode::master_section_end(self.player.level)
+                );
+                panel.push(format!("MODE:          {}", self.mode.get_name()));
+                panel.push(format!(
+                    "GRADE:         {}",
+                    mode::master_grade(self.player.score.points)
+                ));
+                panel.push(format!(
+                    "TIME:          {}",
+                    utils::format_duration(elapsed)
+                ));
+            }
+            GameMode::Zen => {
+                let gravity = if self.gravity_enabled { "ON" } else { "OFF" };
+
+                panel.push(format!("MODE:          {}", self.mode.get_name()));
+                panel.push(String::from("UNDO:          U"));
+                panel.push(String::from("SWAP NEXT:     N"));
+                panel.push(format!("GRAVITY:       G ({gravity})"));
+            }
+            GameMode::Puzzle { .. } => {
+                if let Some(challenge) = self.challenge.as_ref() {
+                    let pieces_left = self.player.random_bag.len()
+                        + usize::from(self.player.next_piece.is_some());
+
+                    panel.push(format!("MODE:          {}", self.mode.get_name()));
+                    panel.push(format!(
+                        "CHALLENGE:     {} / {}",
+                        challenge.number,
+                        puzzle::count_challenges()
+                    ));
+                    panel.push(format!("               {}", challenge.title.to_uppercase()));
+                    panel.push(format!("GOAL:          {}", challenge.goal));
+                    panel.push(format!("PIECES LEFT:   {pieces_left}"));
+                }
+            }
+            GameMode::PerfectClear => {
+                if let Some(training) = self.training.as_ref() {
+                    panel.push(format!("MODE:          {}", self.mode.get_name()));
+                    panel.push(format!("OPENER:        {}", training.get_name()));
+                    panel.push(format!(
+                        "PIECES:        {} / {}",
+                        training.get_pieces_placed(),
+                        training.get_max_pieces()
+                    ));
+                    panel.push(format!(
+                        "SUCCESS:       {} / {} ({:.0}%)",
+                        training.get_successes(),
+                        training.get_attempts(),
+                        training.get_success_rate()
+                    ));
+                }
+            }
+            GameMode::Drill => {
+                if let Some(finesse) = self.finesse.as_ref() {
+                    panel.push(format!("MODE:          {}", self.mode.get_name()));
+                    panel.push(format!(
+                        "TARGETS HIT:   {} / {}",
+                        finesse.get_hits(),
+                        finesse.get_pieces()
+                    ));
+                }
+            }
+            GameMode::Demo => {
+                if let Some(bot) = self.bot.as_ref() {
+                    panel.push(format!("MODE:          {}", self.mode.get_name()));
+                    panel.push(format!("AI LEVEL:      {}", bot.get_level().get_name()));
+                }
+            }
+            GameMode::Marathon | GameMode::Versus { .. } => {}
+        }
+
+        if let Some(finesse) = self.finesse.as_ref() {
+            panel.push(format!(
+                "FAULTS:        {} / {}",
+                finesse.get_faults(),
+                finesse.get_pieces()
+            ));
+            if let Some((inputs, least)) = finesse.get_last_piece() {
+                panel.push(format!("LAST PIECE:    {inputs} INPUTS ({least} NEEDED)"));
+            }
+        }
+
+        if let Some(name) = self.bot.as_ref().and_then(|bot| bot.get_external_name()) {
+            panel.push(format!("BOT:           {}", name.to_uppercase()));
+        }
+
+        if self.mode != GameMode::Drill && self.bot.is_none() {
+            let hints = if self.hints_enabled { "ON" } else { "OFF" };
+            panel.push(format!("HINT:          H ({hints})"));
+            if let Some(hint) = self.hint.as_ref().filter(|_| self.hints_enabled) {
+                let explanation = hint.explanation.to_uppercase();
+                for line in utils::wrap_words(&explanation, HINT_WIDTH) {
+                    panel.push(format!("               {line}"));
+                }
+            }
+        }
+
+        let mut lines = panel.into_iter().map(Line::from).collect::<Vec<Line>>();
+        lines[5].push_tiles(vec![Tile::Arrow(Direction::Left)]);
+        lines[6].push_tiles(vec![Tile::Arrow(Direction::Right)]);
+        lines[7].push_tiles(vec![Tile::Arrow(Direction::Down)]);
+        if let Some(row) = meter_row {
+            lines[row].push_tiles(self.warning_meter());
         }
 
-        let tetromino = self.random_bag.pop().unwrap_or(Tetromino::O);
-        let position = tetromino.get_spawn_position();
+        lines
+    }
 
-        Piece::new(tetromino, position)
+    fn game_view(&self, board: &Board) -> View {
+        let mut view = View::default();
+        if self.pause_menu.is_some() {
+            view.add_column(0, render::message_lines(&self.get_menu_lines()));
+        } else {
+            let mut tiles = render::board_tiles(board);
+            self.animations.apply(&mut tiles);
+            view.add_column(0, render::well_lines(tiles));
+        }
+        view.add_column(PANEL_COLUMN, self.side_panel());
+        if let Some(next_piece) = self.player.next_piece.as_ref()
+            && self.pause_menu.is_none()
+        {
+            view.add_column(PREVIEW_COLUMN, render::piece_lines(next_piece));
+        }
+        if self.stats_shown {
+            view.add_column(STATS_COLUMN, self.stats_panel());
+        }
+
+        view
     }
 
-    fn refill_random_bag(&mut self) {
-        let mut bag = vec![
-            Tetromino::I,
-            Tetromino::J,
-            Tetromino::L,
-            Tetromino::O,
-            Tetromino::S,
-            Tetromino::T,
-            Tetromino::Z,
+    fn stats_panel(&self) -> Vec<Line> {
+        let elapsed = self.finished_in.unwrap_or_else(|| self.get_play_time());
+        let stats = &self.stats;
+
+        let mut panel = vec![
+            String::from("STATISTICS:   S"),
+            format!("TIME:         {}", utils::format_duration(elapsed)),
+            format!("PIECES:       {}", stats.get_pieces()),
+            format!("PPS:          {:.2}", stats.get_pps(elapsed)),
+            format!("KPP:          {:.2}", stats.get_kpp()),
+            format!("APM:          {:.1}", stats.get_apm(elapsed)),
+            format!("SINGLES:      {}", stats.get_clears(1)),
+            format!("DOUBLES:      {}", stats.get_clears(2)),
+            format!("TRIPLES:      {}", stats.get_clears(3)),
+            format!("TETRISES:     {}", stats.get_clears(4)),
+            format!("T-SPINS:      {}", stats.get_tspins()),
+            format!("MAX COMBO:    {}", stats.get_max_combo()),
         ];
+        if let Some(finesse) = self.finesse.as_ref() {
+            panel.push(format!(
+                "FINESSE:      {} / {}",
+                finesse.get_pieces() - finesse.get_faults().min(finesse.get_pieces()),
+                finesse.get_pieces()
+            ));
+        }
+        panel.push(String::new());
 
-        self.random_bag = Vec::new();
-        while !bag.is_empty() {
-            let index = self.rng.random_range(0..bag.len());
-            let tetromino = bag.remove(index);
-            self.random_bag.push(tetromino);
+        let mut lines = panel.into_iter().map(Line::from).collect::<Vec<Line>>();
+        // every piece gets a bar as long as its share of the pieces placed
+        let tetrominoes = stats.get_tetrominoes();
+        let most = tetrominoes
+            .iter()
+            .map(|(_, count)| *count)
+            .max()
+            .unwrap_or_default();
+        for (tetromino, count) in tetrominoes {
+            let length = (count * STATS_BAR_MAX)
+                .checked_div(most)
+                .unwrap_or_default();
+            let cell = Cell::try_from(tetromino.to_char()).unwrap_or_default();
+
+            let mut line = Line::from(format!("{}: {count:>4} ", tetromino.to_char()));
+            line.push_tiles(vec![Tile::Cell(cell); length]);
+            lines.push(line);
         }
+
+        lines
+    }
+
+    /// Returns how long the game has been played, pauses excluded.
+    fn get_play_time(&self) -> Duration {
+        let paused_for = self.get_pause_duration().unwrap_or_default();
+        self.started.elapsed().saturating_sub(paused_for)
+    }
+
+    /// Outlines the placement recommended for the active piece.
+    fn update_hint(&mut self, board: &mut Board) {
+        self.hint = hint::suggest(board, self.player.next_piece.as_ref());
+        if let Some(hint) = self.hint.as_ref() {
+            board.set_target(hint.placement.cells.clone());
+        }
+    }
+
+    fn snapshot(&self, board: &Board) -> Snapshot {
+        Snapshot {
+            board: board.clone(),
+            player: self.player.clone(),
+        }
+    }
+
+    /// Scores a lock the way the master mode does: the points depend on the
+    /// level and on the combo of consecutive clearing pieces.
+    fn increment_master_score(&mut self, score: Score) {
+        let lines = score.lines_destroyed as u64;
+
+        if lines == 0 {
+            self.combo = 1;
+            return;
+        }
+
+        self.combo += 2 * lines - 2;
+        self.player.score.increment(Score {
+            lines_destroyed: score.lines_destroyed,
+            points: (self.player.level as u64 + lines).div_ceil(4) * lines * self.combo,
+        });
+
+        self.player.level = MASTER_LEVEL_MAX.min(self.player.level + lines as u32);
+        if self.player.level == MASTER_LEVEL_MAX && self.finished_in.is_none() {
+            self.finished_in = Some(self.get_play_time());
+        }
+    }
+
+    fn get_music_stage(&self, level: u32) -> u32 {
+        if let GameMode::Master = self.mode {
+            level / MASTER_SECTION_LEVELS
+        } else {
+            level
+        }
+    }
+
+    fn warning_meter(&self) -> Vec<Tile> {
+        let progress = self.garbage_rise_timer.elapsed().as_secs_f64()
+            / self.garbage_rise_interval.as_secs_f64();
+        let filled = ((progress * WARNING_METER_SIZE as f64) as u32).min(WARNING_METER_SIZE);
+
+        (0..WARNING_METER_SIZE)
+            .map(|i| Tile::Cell(if i < filled { Cell::Red } else { Cell::Black }))
+            .collect()
     }
 
     fn load_songs(&mut self) -> bool {
@@ -286,3 +1152,172 @@ impl Context {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::{BOARD_HEIGHT, BOARD_WIDTH},
+        pieces::Tetromino,
+    };
+
+    #[test]
+    fn idle_until_the_next_deadline() {
+        let mut context = Context::new(GameMode::Marathon);
+        let next_frame = Instant::now() + utils::FRAME_DURATION;
+
+        // the screen was never drawn, so it's due right away
+        assert_eq!(Duration::ZERO, context.get_idle_time(next_frame));
+
+        context.rendered = Some(Instant::now());
+        let idle = context.get_idle_time(next_frame);
+        assert!(idle > Duration::ZERO && idle <= utils::FRAME_DURATION);
+        assert!(context.get_idle_time(Instant::now()).is_zero());
+    }
+
+    #[test]
+    fn statistics_fit_in_80_columns() {
+        let mut context = Context::new(GameMode::Marathon);
+        context.stats_shown = true;
+        context.enable_finesse();
+        let mut board = Board::from_text("XXXXXXXX..\nXXXXXXXX..").unwrap();
+        board.add_piece(Piece::new(Tetromino::O, Tetromino::O.get_spawn_position()));
+
+        let fits = |context: &Context, rows| {
+            let size = Some((80, rows));
+            let mut renderer =
+                TerminalRenderer::with_output(Vec::new(), Style::Ascii, Theme::default(), size);
+            renderer.render(&context.game_view(&board)).unwrap();
+            !renderer.is_too_small()
+        };
+        assert!(fits(&context, 24));
+
+        // the wide hint lines start below the statistics
+        context.hints_enabled = true;
+        context.hint = Some(Hint {
+            explanation: String::from("clears 2 lines, fills the well and keeps the stack flat"),
+            ..hint::suggest(&board, None).unwrap()
+        });
+        assert!(fits(&context, 30));
+    }
+
+    #[test]
+    fn zen_history_is_capped() {
+        // every placement leaves its number written in binary on the floor
+        let floor = |count: usize| {
+            (0..BOARD_WIDTH)
+                .map(|col| if count >> col & 1 == 1 { 'X' } else { '.' })
+                .collect::<String>()
+        };
+        let mut context = Context::new(GameMode::Zen);
+        let mut board = Board::new();
+        for count in 0..HISTORY_MAX + 20 {
+            board = Board::from_text(&floor(count)).unwrap();
+            board.add_piece(context.get_piece().unwrap());
+            context.piece_spawned(&mut board);
+        }
+        assert_eq!(HISTORY_MAX, context.history.len());
+
+        // the placements within the cap can still be undone, one at a time
+        let written = |board: &Board| {
+            (0..BOARD_WIDTH)
+                .map(|col| board.get_cell(BOARD_HEIGHT - 1, col))
+                .collect::<Vec<Cell>>()
+        };
+        assert!(context.undo(&mut board));
+        let expected = Board::from_text(&floor(HISTORY_MAX + 18)).unwrap();
+        assert_eq!(written(&expected), written(&board));
+
+        let mut undone = 1;
+        while context.undo(&mut board) {
+            undone += 1;
+        }
+        assert_eq!(HISTORY_MAX - 1, undone);
+        let expected = Board::from_text(&floor(20)).unwrap();
+        assert_eq!(written(&expected), written(&board));
+    }
+
+    #[test]
+    fn menu_keys_act_on_press_only() {
+        let mut context = Context::new(GameMode::Marathon);
+        let mut board = Board::new();
+        let key = |code, kind| Event::Key(KeyEvent::new_with_kind(code, KeyModifiers::NONE, kind));
+        let mut press = |context: &mut Context, code| {
+            for kind in [KeyEventKind::Press, KeyEventKind::Release] {
+                context
+                    .pause_menu_event(&mut board, key(code, kind))
+                    .unwrap();
+            }
+        };
+        let selected = |context: &Context| {
+            context
+                .get_menu_lines()
+                .into_iter()
+                .find(|line| line.starts_with('>'))
+        };
+
+        context.pause();
+        press(&mut context, KeyCode::Down);
+        assert_eq!(Some(String::from("> RESTART         ")), selected(&context));
+
+        // out of the options page, and still paused
+        press(&mut context, KeyCode::Down);
+        press(&mut context, KeyCode::Enter);
+        press(&mut context, KeyCode::Esc);
+        assert!(context.is_paused());
+        assert_eq!(Some(String::from("> OPTIONS         ")), selected(&context));
+    }
+
+    #[test]
+    fn suspend_pauses_the_clocks() {
+        let mut context = Context::new(GameMode::Survival);
+        let started = context.started;
+        let garbage_rise_timer = context.garbage_rise_timer;
+
+        context.suspend().unwrap();
+        assert!(context.is_paused());
+        std::thread::sleep(Duration::from_millis(50));
+        context.resume();
+
+        assert!(context.started >= started + Duration::from_millis(50));
+        assert!(context.garbage_rise_timer >= garbage_rise_timer + Duration::from_millis(50));
+        assert!(context.get_play_time() < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn master_score() {
+        let mut context = Context::new(GameMode::Master);
+        context.player.level = 10;
+
+        // a double right after a single keeps the combo going
+        context.increment_score(Score {
+            lines_destroyed: 1,
+            points: 0,
+        });
+        assert_eq!(3, context.player.score.points);
+        assert_eq!(11, context.player.level);
+        context.increment_score(Score {
+            lines_destroyed: 2,
+            points: 0,
+        });
+        assert_eq!(3 + 4 * 2 * 3, context.player.score.points);
+        assert_eq!(13, context.player.level);
+
+        context.increment_score(Score::default());
+        assert_eq!(1, context.combo);
+
+        // new pieces stop at the end of the section, lines don't
+        context.player.level = 98;
+        context.piece_spawned(&mut Board::new());
+        context.piece_spawned(&mut Board::new());
+        assert_eq!(99, context.player.level);
+
+        context.player.level = 998;
+        context.increment_score(Score {
+            lines_destroyed: 4,
+            points: 0,
+        });
+        assert_eq!(MASTER_LEVEL_MAX, context.player.level);
+        assert!(context.is_finished());
+    }
+}
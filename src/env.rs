@@ -0,0 +1,502 @@
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    board::{Board, Placement, BOARD_HEIGHT, BOARD_WIDTH},
+    mode::{FrameTimers, GameMode, Timings},
+    pieces::{Cell, Tetromino},
+    player::Player,
+    utils::{Input, LockResult},
+};
+
+/// How the agent moves the pieces.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionSpace {
+    /// One input per step, the game advancing by some frames in between.
+    #[default]
+    Primitive,
+    /// One piece per step, dropped at one of the placements listed in the
+    /// observation.
+    Placement,
+}
+
+/// An action of the agent, written in JSON as `"wait"`, `{"input": "left"}`
+/// or `{"placement": 3}`.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Lets the frames go by, without touching the piece.
+    Wait,
+    Input(Input),
+    /// Index of the placement to play, in the list of the observation.
+    Placement(usize),
+}
+
+/// Rewards given every time a piece locks, the ones for holes and height
+/// being given per hole created and per row the stack grew.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Rewards {
+    /// By number of lines cleared at once.
+    pub lines: [f64; 5],
+    pub tspin: f64,
+    pub perfect_clear: f64,
+    pub hole: f64,
+    pub height: f64,
+    pub piece: f64,
+    pub game_over: f64,
+}
+
+impl Default for Rewards {
+    fn default() -> Self {
+        Self {
+            lines: [0.0, 1.0, 3.0, 5.0, 8.0],
+            tspin: 2.0,
+            perfect_clear: 10.0,
+            hole: -0.5,
+            height: -0.1,
+            piece: 0.01,
+            game_over: -10.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub action_space: ActionSpace,
+    /// Frames of gravity after every primitive action.
+    pub frames_per_step: u32,
+    /// Steps after which the episode ends, if any.
+    pub max_steps: Option<usize>,
+    pub rewards: Rewards,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            action_space: ActionSpace::default(),
+            frames_per_step: 1,
+            max_steps: None,
+            rewards: Rewards::default(),
+        }
+    }
+}
+
+/// What the agent sees of the game after every step.
+#[derive(Clone, Debug, Serialize)]
+pub struct Observation {
+    /// The board without the active piece.
+    pub cells: [[Cell; BOARD_WIDTH]; BOARD_HEIGHT],
+    /// Whether each cell of the board is taken, the active piece excluded.
+    pub occupancy: [[bool; BOARD_WIDTH]; BOARD_HEIGHT],
+    pub piece: Option<Tetromino>,
+    /// Cells of the active piece on the board.
+    pub piece_cells: Vec<(usize, usize)>,
+    /// The pieces coming next, as far as the game shows them.
+    pub queue: Vec<Tetromino>,
+    /// Always empty, since there's no hold piece in textris.
+    pub hold: Option<Tetromino>,
+    /// Placements of the active piece, in the placement action space only.
+    pub placements: Vec<Placement>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Info {
+    pub lines: usize,
+    pub points: u64,
+    pub pieces: usize,
+    pub steps: usize,
+    /// Whether the episode ended because of the step limit, not a top out.
+    pub truncated: bool,
+    /// Lines cleared by the piece locked during the step, if any.
+    pub lock_lines: Option<usize>,
+    pub tspin: bool,
+    pub perfect_clear: bool,
+}
+
+/// A headless game for training agents, in the style of a Gym environment:
+/// the game only advances when the agent acts. It follows the rules of the
+/// marathon mode, except for the entry delay, the next piece entering the
+/// board right away.
+pub struct Env {
+    board: Board,
+    config: Config,
+    done: bool,
+    holes: usize,
+    height: usize,
+    info: Info,
+    placements: Vec<Placement>,
+    player: Player,
+    timers: FrameTimers,
+    timings: Timings,
+}
+
+impl Env {
+    pub fn new(config: Config) -> Self {
+        Self {
+            board: Board::new(),
+            config,
+            done: true,
+            holes: 0,
+            height: 0,
+            info: Info::default(),
+            placements: Vec::new(),
+            player: Player::new(),
+            timers: FrameTimers::default(),
+            timings: GameMode::Marathon.get_timings(),
+        }
+    }
+
+    /// Starts a new episode, the pieces being dealt the same way every time for
+    /// the same seed.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.board = Board::new();
+        self.done = false;
+        self.holes = 0;
+        self.height = 0;
+        self.info = Info::default();
+        self.player = Player::with_seed(seed);
+        self.timers = FrameTimers::default();
+        self.spawn_piece();
+
+        self.observe()
+    }
+
+    /// Plays an action, returning what the agent sees afterwards, the reward,
+    /// whether the episode is over, and some details about the game.
+    pub fn step(&mut self, action: Action) -> Result<(Observation, f64, bool, Info), String> {
+        if self.done {
+            return Err(String::from("the episode is over, reset the environment"));
+        }
+
+        let mut lock = None;
+        match (self.config.action_space, action) {
+            (ActionSpace::Primitive, Action::Wait) => {}
+            (ActionSpace::Primitive, Action::Input(input)) => {
+                let had_piece = self.board.has_piece();
+                let score = self.board.apply_input(input);
+                if had_piece && !self.board.has_piece() {
+                    lock = Some(score);
+                }
+            }
+            (ActionSpace::Placement, Action::Placement(index)) => {
+                let placement = self
+                    .placements
+                    .get(index)
+                    .ok_or_else(|| format!("there is no placement {index}"))?;
+                for input in &placement.inputs {
+                    if *input == Input::SoftDrop {
+                        self.board.drop_piece();
+                    } else {
+                        self.board.apply_input(*input);
+                    }
+                }
+                lock = Some(self.board.land_piece());
+            }
+            (space, action) => {
+                return Err(format!("{action:?} is not in the {space:?} action space"));
+            }
+        }
+
+        if self.config.action_space == ActionSpace::Primitive && lock.is_none() {
+            let gravity = self.player.get_gravity();
+            for _ in 0..self.config.frames_per_step {
+                lock = self.timers.step(&mut self.board, gravity, &self.timings);
+                if lock.is_some() {
+                    break;
+                }
+            }
+        }
+
+        self.info.steps += 1;
+        self.info.lock_lines = None;
+        self.info.tspin = false;
+        self.info.perfect_clear = false;
+
+        let mut reward = 0.0;
+        if let Some(score) = lock {
+            reward += self.reward(&self.board.get_last_lock().clone());
+            self.player.increment_score(score);
+            self.info.lines = self.player.score.lines_destroyed;
+            self.info.points = self.player.score.points;
+            self.info.pieces += 1;
+            self.timers = FrameTimers::default();
+
+            if !self.spawn_piece() {
+                self.done = true;
+                reward += self.config.rewards.game_over;
+            }
+        }
+
+        if let Some(max_steps) = self.config.max_steps
+            && self.info.steps >= max_steps
+            && !self.done
+        {
+            self.done = true;
+            self.info.truncated = true;
+        }
+
+        Ok((self.observe(), reward, self.done, self.info.clone()))
+    }
+}
+
+// Private functions
+impl Env {
+    fn spawn_piece(&mut self) -> bool {
+        let added = self
+            .player
+            .get_piece()
+            .is_some_and(|piece| self.board.add_piece(piece));
+
+        self.placements = match self.board.get_piece() {
+            Some(piece) if self.config.action_space == ActionSpace::Placement => {
+                self.board.legal_placements(piece)
+            }
+            _ => Vec::new(),
+        };
+
+        added
+    }
+
+    fn reward(&mut self, lock: &LockResult) -> f64 {
+        let rewards = &self.config.rewards;
+        let holes = self.board.count_holes();
+        let height = self.board.get_stack_height();
+
+        let reward = rewards.lines[lock.lines.min(4)]
+            + if lock.tspin { rewards.tspin } else { 0.0 }
+            + if lock.perfect_clear {
+                rewards.perfect_clear
+            } else {
+                0.0
+            }
+            + rewards.hole * (holes as f64 - self.holes as f64)
+            + rewards.height * (height as f64 - self.height as f64)
+            + rewards.piece;
+
+        self.holes = holes;
+        self.height = height;
+        self.info.lock_lines = Some(lock.lines);
+        self.info.tspin = lock.tspin;
+        self.info.perfect_clear = lock.perfect_clear;
+
+        reward
+    }
+
+    fn observe(&self) -> Observation {
+        let cells = *self.board.get_grid();
+        let occupancy = cells.map(|row| row.map(|cell| cell != Cell::Black));
+        let piece = self.board.get_piece();
+
+        Observation {
+            cells,
+            occupancy,
+            piece: piece.map(|p| p.get_tetromino().clone()),
+            piece_cells: piece.map_or_else(Vec::new, |p| {
+                p.get_cells()
+                    .into_iter()
+                    .filter_map(|(row, col)| Some((row.try_into().ok()?, col.try_into().ok()?)))
+                    .collect()
+            }),
+            queue: self
+                .player
+                .next_piece
+                .iter()
+                .map(|p| p.get_tetromino().clone())
+                .collect(),
+            hold: None,
+            placements: self.placements.clone(),
+        }
+    }
+}
+
+/// Requests read by `serve`, one JSON object per line.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    /// Replaces the configuration, from the next episode on.
+    Configure {
+        config: Config,
+    },
+    Reset {
+        seed: Option<u64>,
+    },
+    Step {
+        action: Action,
+    },
+    Close,
+}
+
+/// Runs the environment for an agent living in another process, such as a
+/// Python training script: every request line gets a response line, either
+/// `{"observation": ...}` after a reset, `{"observation": ..., "reward": ...,
+/// "done": ..., "info": ...}` after a step, or `{"error": ...}`.
+pub fn serve(input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    let mut env = Env::new(Config::default());
+
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Configure { config }) => {
+                env.config = config;
+                env.done = true;
+                json!({ "ok": true })
+            }
+            Ok(Request::Reset { seed }) => {
+                let seed = seed.unwrap_or_else(rand::random);
+                json!({ "observation": env.reset(seed) })
+            }
+            Ok(Request::Step { action }) => match env.step(action) {
+                Ok((observation, reward, done, info)) => json!({
+                    "observation": observation,
+                    "reward": reward,
+                    "done": done,
+                    "info": info,
+                }),
+                Err(error) => json!({ "error": error }),
+            },
+            Ok(Request::Close) => break,
+            Err(error) => json!({ "error": format!("invalid request: {error}") }),
+        };
+
+        writeln!(output, "{response}")?;
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn same_seed_same_pieces() {
+        let mut env = Env::new(Config::default());
+        let pieces = |env: &mut Env| {
+            let observation = env.reset(7);
+            (observation.piece, observation.queue)
+        };
+
+        let first = pieces(&mut env);
+        assert_eq!(first, pieces(&mut env));
+        assert!(first.0.is_some());
+        assert_eq!(1, first.1.len());
+    }
+
+    #[test]
+    fn primitive_actions() {
+        let mut env = Env::new(Config::default());
+        let observation = env.reset(1);
+        let cells = observation.piece_cells.clone();
+
+        let (observation, reward, done, _) = env.step(Action::Input(Input::Left)).unwrap();
+        assert!(!done);
+        assert_eq!(0.0, reward);
+        assert!(observation
+            .piece_cells
+            .iter()
+            .zip(&cells)
+            .all(|(a, b)| a.1 + 1 == b.1));
+        assert!(env.step(Action::Placement(0)).is_err());
+
+        let (observation, reward, done, info) = env.step(Action::Input(Input::HardDrop)).unwrap();
+        assert!(!done);
+        assert_eq!(1, info.pieces);
+        assert_eq!(
+            4,
+            observation
+                .occupancy
+                .iter()
+                .flatten()
+                .filter(|c| **c)
+                .count()
+        );
+        assert!(reward < 0.0);
+    }
+
+    #[test]
+    fn placement_actions_until_game_over() {
+        let config = Config {
+            action_space: ActionSpace::Placement,
+            ..Config::default()
+        };
+        let mut env = Env::new(config);
+        let mut observation = env.reset(3);
+
+        // always stacking at the first placement tops out quickly
+        let mut steps = 0;
+        loop {
+            assert!(!observation.placements.is_empty());
+            let (next, reward, done, info) = env.step(Action::Placement(0)).unwrap();
+            steps += 1;
+            assert_eq!(steps, info.pieces);
+            if done {
+                assert!(!info.truncated);
+                assert!(reward <= env.config.rewards.game_over);
+                break;
+            }
+            observation = next;
+        }
+        assert!(env.step(Action::Wait).is_err());
+    }
+
+    #[test]
+    fn serve_requests() {
+        let requests = [
+            r#"{"type":"configure","config":{"action_space":"placement","max_steps":2}}"#,
+            r#"{"type":"reset","seed":5}"#,
+            r#"{"type":"step","action":{"placement":0}}"#,
+            r#"{"type":"step","action":"wait"}"#,
+            r#"{"type":"step","action":{"placement":0}}"#,
+            r#"{"type":"close"}"#,
+        ];
+        let mut output = Vec::new();
+        serve(requests.join("\n").as_bytes(), &mut output).unwrap();
+
+        let responses = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(5, responses.len());
+        assert!(responses[1]["observation"]["cells"][19][0] == ".");
+        assert_eq!(false, responses[2]["done"]);
+        assert!(responses[3]["error"].is_string());
+        assert_eq!(true, responses[4]["done"]);
+        assert_eq!(true, responses[4]["info"]["truncated"]);
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --ignored --nocapture"]
+    fn bench_steps() {
+        let mut env = Env::new(Config::default());
+        env.reset(0);
+
+        let started = Instant::now();
+        let mut steps = 0;
+        while started.elapsed().as_secs() < 2 {
+            let action = match steps % 4 {
+                0 => Action::Input(Input::Left),
+                1 => Action::Input(Input::RotateClockwise),
+                2 => Action::Wait,
+                _ => Action::Input(Input::HardDrop),
+            };
+            if env.step(action).unwrap().2 {
+                env.reset(steps);
+            }
+            steps += 1;
+        }
+        println!("{:.0} primitive steps per second", steps as f64 / 2.0);
+    }
+}
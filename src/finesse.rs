@@ -0,0 +1,174 @@
+use rand::Rng;
+
+use crate::{
+    board::{Board, Placement},
+    utils::{Input, LockResult},
+};
+
+/// Counts the inputs spent on every piece, and compares them with the least
+/// inputs needed for the same placement. In drill mode, every piece comes
+/// with a random target placement to reach.
+pub struct Finesse {
+    drill: bool,
+    faults: usize,
+    hits: usize,
+    inputs: usize,
+    last_input: Option<Input>,
+    last_piece: Option<(usize, usize)>,
+    pieces: usize,
+    placements: Vec<Placement>,
+    target: Option<Placement>,
+}
+
+impl Finesse {
+    pub fn new(drill: bool) -> Self {
+        Self {
+            drill,
+            faults: 0,
+            hits: 0,
+            inputs: 0,
+            last_input: None,
+            last_piece: None,
+            pieces: 0,
+            placements: Vec::new(),
+            target: None,
+        }
+    }
+
+    /// Searches the placements of the piece which just entered the board, and
+    /// picks the target to reach when drilling.
+    pub fn piece_spawned(&mut self, board: &mut Board, rng: &mut impl Rng) {
+        self.inputs = 0;
+        self.last_input = None;
+        self.placements = board
+            .get_piece()
+            .map(|piece| board.legal_placements(piece))
+            .unwrap_or_default();
+
+        if self.drill && !self.placements.is_empty() {
+            let target = self.placements[rng.random_range(0..self.placements.len())].clone();
+            board.set_target(target.cells.clone());
+            self.target = Some(target);
+        }
+    }
+
+    /// Counts an input of the player. Holding the soft drop counts once, while
+    /// the hard drop is never counted.
+    pub fn record_input(&mut self, input: Input) {
+        let repeated_drop = input == Input::SoftDrop && self.last_input == Some(Input::SoftDrop);
+        if input != Input::HardDrop && !repeated_drop {
+            self.inputs += 1;
+        }
+
+        self.last_input = Some(input);
+    }
+
+    /// Compares the inputs spent on the piece that was just locked with the
+    /// least inputs needed for its placement.
+    pub fn piece_locked(&mut self, lock: &LockResult) {
+        // the piece may have been pushed around by gravity or garbage in a way
+        // the search doesn't know of, such placements are not judged
+        let Some(placement) = self.placements.iter().find(|p| p.cells == lock.cells) else {
+            return;
+        };
+
+        let fault = self.inputs > placement.count_inputs();
+        self.pieces += 1;
+        self.faults += usize::from(fault);
+        self.last_piece = Some((self.inputs, placement.count_inputs()));
+
+        if let Some(target) = self.target.take()
+            && target.cells == lock.cells
+            && !fault
+        {
+            self.hits += 1;
+        }
+    }
+
+    pub fn is_drill(&self) -> bool {
+        self.drill
+    }
+
+    pub fn get_faults(&self) -> usize {
+        self.faults
+    }
+
+    pub fn get_hits(&self) -> usize {
+        self.hits
+    }
+
+    pub fn get_pieces(&self) -> usize {
+        self.pieces
+    }
+
+    /// Returns the inputs spent on the last judged piece, and the least inputs
+    /// its placement needed.
+    pub fn get_last_piece(&self) -> Option<(usize, usize)> {
+        self.last_piece
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::{Piece, Tetromino};
+
+    fn spawn(tetromino: Tetromino) -> Board {
+        let mut board = Board::new();
+        let position = tetromino.get_spawn_position();
+        assert!(board.add_piece(Piece::new(tetromino, position)));
+
+        board
+    }
+
+    #[test]
+    fn placements_on_empty_board() {
+        let mut board = spawn(Tetromino::O);
+        let mut finesse = Finesse::new(false);
+        finesse.piece_spawned(&mut board, &mut rand::rng());
+
+        // the O piece fits in 9 columns, and never needs more than 4 moves
+        assert_eq!(9, finesse.placements.len());
+        assert!(finesse.placements[0].inputs.is_empty());
+        assert!(finesse.placements.iter().all(|p| p.inputs.len() <= 4));
+    }
+
+    #[test]
+    fn fault_on_extra_inputs() {
+        let mut board = spawn(Tetromino::O);
+        let mut finesse = Finesse::new(false);
+        finesse.piece_spawned(&mut board, &mut rand::rng());
+
+        for input in [Input::Left, Input::Right, Input::Right, Input::HardDrop] {
+            finesse.record_input(input);
+            board.apply_input(input);
+        }
+        finesse.piece_locked(board.get_last_lock());
+
+        assert_eq!(1, finesse.get_pieces());
+        assert_eq!(1, finesse.get_faults());
+        assert_eq!(Some((3, 1)), finesse.get_last_piece());
+    }
+
+    #[test]
+    fn drill_targets() {
+        let mut finesse = Finesse::new(true);
+
+        for (target, hits) in [(0, 1), (1, 1)] {
+            let mut board = spawn(Tetromino::T);
+            finesse.piece_spawned(&mut board, &mut rand::rng());
+            assert!(finesse.target.is_some());
+
+            // the first placement is the one right below the spawn position
+            finesse.target = Some(finesse.placements[target].clone());
+            finesse.record_input(Input::HardDrop);
+            board.apply_input(Input::HardDrop);
+            finesse.piece_locked(board.get_last_lock());
+
+            assert_eq!(hits, finesse.get_hits());
+        }
+
+        assert_eq!(2, finesse.get_pieces());
+        assert_eq!(0, finesse.get_faults());
+    }
+}
@@ -0,0 +1,44 @@
+use rand::{rngs::ThreadRng, Rng};
+
+use crate::{
+    board::{Board, BOARD_WIDTH},
+    pieces::Cell,
+};
+
+/// Produces garbage rows, each having a single hole. Every new row keeps the
+/// hole of the previous one, unless the hole change probability says otherwise.
+pub struct GarbageGenerator {
+    hole_change_probability: f64,
+    hole_col: Option<usize>,
+    rng: ThreadRng,
+}
+
+impl GarbageGenerator {
+    pub fn new(hole_change_probability: f64) -> Self {
+        Self {
+            hole_change_probability: hole_change_probability.clamp(0.0, 1.0),
+            hole_col: None,
+            rng: rand::rng(),
+        }
+    }
+
+    pub fn next_hole(&mut self) -> usize {
+        let hole_col = match self.hole_col {
+            None => self.rng.random_range(0..BOARD_WIDTH),
+            Some(prev) if self.rng.random_bool(self.hole_change_probability) => {
+                // pick any other column, so that the hole really changes
+                (prev + self.rng.random_range(1..BOARD_WIDTH)) % BOARD_WIDTH
+            }
+            Some(prev) => prev,
+        };
+
+        self.hole_col = Some(hole_col);
+        hole_col
+    }
+
+    pub fn next_rows(&mut self, count: usize) -> Vec<[Cell; BOARD_WIDTH]> {
+        (0..count)
+            .map(|_| Board::garbage_row(self.next_hole()))
+            .collect()
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    board::{Board, Placement},
+    bot::{self, Bot, BotLevel, Weights},
+    pieces::Piece,
+};
+
+/// Well depth from which filling the well is worth a mention.
+const WELL_DEPTH_MIN: usize = 2;
+
+/// The placement recommended for the active piece, and why.
+pub struct Hint {
+    pub placement: Placement,
+    pub explanation: String,
+}
+
+/// Picks the best placement of the active piece with the evaluation of the
+/// bot, looking one piece ahead, and explains what it does to the stack.
+pub fn suggest(board: &Board, next_piece: Option<&Piece>) -> Option<Hint> {
+    let bot = Bot::new(BotLevel::default(), Weights::default());
+    let placement = bot.find_best(board, next_piece.map(|p| p.get_tetromino()))?;
+    let (after, lines) = bot::play(board, &placement)?;
+    let explanation = explain(board, &placement, &after, lines);
+
+    Some(Hint {
+        placement,
+        explanation,
+    })
+}
+
+/// Describes the effect of a placement in a few words, such as "fills well,
+/// creates 0 holes".
+fn explain(before: &Board, placement: &Placement, after: &Board, lines: usize) -> String {
+    let mut reasons = Vec::new();
+
+    if placement.tspin {
+        reasons.push(String::from("T-spin"));
+    }
+    match lines {
+        0 => {}
+        1 => reasons.push(String::from("clears 1 line")),
+        _ => reasons.push(format!("clears {lines} lines")),
+    }
+
+    let wells = before.get_well_depths();
+    if placement
+        .cells
+        .iter()
+        .any(|&(_, col)| wells[col] >= WELL_DEPTH_MIN)
+    {
+        reasons.push(String::from("fills well"));
+    }
+    if after.get_bumpiness() < before.get_bumpiness() {
+        reasons.push(String::from("flattens stack"));
+    }
+
+    let holes = after.count_holes().saturating_sub(before.count_holes());
+    match holes {
+        1 => reasons.push(String::from("creates 1 hole")),
+        _ => reasons.push(format!("creates {holes} holes")),
+    }
+
+    reasons.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pieces::Tetromino;
+
+    fn spawn(text: &str, tetromino: Tetromino) -> Board {
+        let mut board = Board::from_text(text).unwrap();
+        let position = tetromino.get_spawn_position();
+        assert!(board.add_piece(Piece::new(tetromino, position)));
+
+        board
+    }
+
+    #[test]
+    fn fills_the_well() {
+        let board = spawn("XXXX.XXXX.\nXXXX.XXXXX\nXXXX.XXXXX", Tetromino::I);
+
+        let hint = suggest(&board, None).unwrap();
+        assert_eq!(
+            vec![(16, 4), (17, 4), (18, 4), (19, 4)],
+            hint.placement.cells
+        );
+        assert_eq!(
+            "clears 2 lines, fills well, flattens stack, creates 0 holes",
+            hint.explanation
+        );
+    }
+
+    #[test]
+    fn flat_placement() {
+        let board = spawn("XX..XXXX..\nXXX.XXXXX.", Tetromino::O);
+
+        let hint = suggest(&board, None).unwrap();
+        assert!(hint.explanation.ends_with("creates 0 holes"));
+        assert!(!hint.explanation.contains("fills well"));
+    }
+}
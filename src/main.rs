@@ -1,71 +1,341 @@
+mod animation;
 mod board;
+mod bot;
 mod context;
+mod env;
+mod finesse;
+mod garbage;
+mod hint;
+mod mode;
+mod narration;
+mod net;
+mod options;
+mod pause;
 mod pieces;
+mod player;
+mod puzzle;
+mod render;
+mod stats;
+mod tbp;
+mod terminal;
+mod theme;
+mod training;
 mod utils;
+mod versus;
 
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
-use crossterm::event::{poll, read, Event, KeyCode};
+use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers};
 
 use board::Board;
+use bot::Bot;
 use context::Context;
-use utils::{Direction, Score};
-
-const PIECE_DROP_MICROSECONDS: f64 = 1_000_000.0;
+use garbage::GarbageGenerator;
+use mode::{FrameTimers, GameMode, Timings, SURVIVAL_HOLE_CHANGE_PROBABILITY};
+use narration::NarrationRenderer;
+use net::{Connection, Network};
+use options::{Options, USAGE};
+use pause::Item;
+use puzzle::Challenge;
+use render::TerminalRenderer;
+use theme::Theme;
+use training::Training;
+use utils::{Input, Score, FRAME_DURATION};
 
 fn main() -> std::io::Result<()> {
-    let mut context = Context::new();
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("textris: {error}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{USAGE}");
+        return Ok(());
+    }
+    if options.env {
+        return env::serve(std::io::stdin().lock(), std::io::stdout().lock());
+    }
+
+    let mut context = Context::new(options.mode);
+    let theme = match options.theme.as_deref().map(Theme::load) {
+        Some(Ok(theme)) => theme,
+        Some(Err(error)) => {
+            eprintln!("textris: {error}");
+            std::process::exit(1);
+        }
+        None => Theme::default(),
+    };
+    if options.narrate {
+        context.set_renderer(Box::new(NarrationRenderer::new()));
+    } else {
+        context.set_renderer(Box::new(TerminalRenderer::new(options.style, theme)));
+    }
+    if options.finesse {
+        context.enable_finesse();
+    }
+    if options.stats {
+        context.stats_toggle();
+    }
+    let Setup {
+        board,
+        bot,
+        connection,
+    } = match prepare(&options, &mut context) {
+        Ok(setup) => setup,
+        Err(error) => {
+            // exiting skips the destructors, the external bot has to go first
+            drop(context);
+            eprintln!("textris: {error}");
+            std::process::exit(1);
+        }
+    };
+
+    terminal::install_panic_hook();
     context.setup()?;
-    game_loop(&mut context)?;
-    context.teardown()
+    let summary = match options.mode {
+        GameMode::Versus { .. } if let Some((connection, rounds)) = connection => {
+            net::game_loop(&mut context, connection, rounds)?
+        }
+        GameMode::Versus { rounds } => versus::game_loop(&mut context, rounds, bot)?,
+        _ => {
+            game_loop(&mut context, board)?;
+            context.get_summary()
+        }
+    };
+    context.teardown()?;
+
+    if let Some(summary) = summary {
+        println!("{summary}");
+    }
+
+    Ok(())
+}
+
+/// What the game starts with, once the options are carried out.
+struct Setup {
+    board: Board,
+    /// The computer player of the versus mode.
+    bot: Option<Bot>,
+    connection: Option<(Connection, usize)>,
 }
 
-fn game_loop(context: &mut Context) -> std::io::Result<()> {
-    let mut board = Board::new();
-    let mut paused = false;
-    let mut speed_micros = context.get_game_speed_micros();
-    let mut now = Instant::now();
+/// Spawns the bots, loads the board of the game mode and connects to the
+/// opponent. On error, the bots are dropped along with the context, which
+/// ends the external bot process.
+fn prepare(options: &Options, context: &mut Context) -> Result<Setup, String> {
+    let mut bot = match options.bot.as_deref() {
+        Some(command) => Some(Bot::external(
+            options.ai.unwrap_or_default(),
+            tbp::Client::spawn(command)?,
+        )),
+        None => options
+            .ai
+            .map(|level| Bot::new(level, options.ai_weights.clone())),
+    };
+    // in versus mode the computer only plays the right player
+    let versus = matches!(options.mode, GameMode::Versus { .. });
+    if !versus && (options.mode == GameMode::Demo || options.bot.is_some()) {
+        let level = options.ai.unwrap_or_default();
+        context.start_bot(
+            bot.take()
+                .unwrap_or_else(|| Bot::new(level, options.ai_weights.clone())),
+        );
+    }
+
+    let board = match options.mode {
+        GameMode::Puzzle { challenge } => {
+            let unlocked = puzzle::load_progress();
+            let number = challenge.unwrap_or(unlocked.min(puzzle::count_challenges()));
+            if number > unlocked {
+                return Err(format!(
+                    "challenge {number} is locked, solve challenge {unlocked} first"
+                ));
+            }
+
+            context.start_challenge(Challenge::load(number)?)
+        }
+        GameMode::PerfectClear => {
+            let opener = Training::load_opener(&options.opener)?;
+            context.start_training(Training::new(&options.opener, opener, options.bag.clone()))
+        }
+        _ => Board::new(),
+    };
+
+    let connection = match (options.mode, options.network.clone()) {
+        (GameMode::Versus { rounds }, Some(network)) => Some(connect(network, rounds)?),
+        _ => None,
+    };
+
+    Ok(Setup {
+        board,
+        bot,
+        connection,
+    })
+}
+
+/// Connects to the opponent of a networked match, and returns the connection
+/// along with the number of rounds of the match.
+fn connect(network: Network, rounds: usize) -> Result<(Connection, usize), String> {
+    match network {
+        Network::Host(address) => {
+            println!("Waiting for an opponent on {address}...");
+            Connection::host(&address, rounds).map(|connection| (connection, rounds))
+        }
+        Network::Join(address) => {
+            println!("Joining the match on {address}...");
+            Connection::join(&address)
+        }
+    }
+}
+
+fn game_loop(context: &mut Context, mut board: Board) -> std::io::Result<()> {
+    // the pause menu may start the game over
+    while play_game(context, board)? {
+        board = context.restart();
+    }
+
+    Ok(())
+}
+
+/// Plays one game until it's over, and tells whether to start over.
+fn play_game(context: &mut Context, mut board: Board) -> std::io::Result<bool> {
+    let mut generator = match context.get_mode() {
+        GameMode::Dig { rows, style } => {
+            let mut generator = GarbageGenerator::new(style.hole_change_probability());
+            board.insert_rows(&generator.next_rows(rows));
+            generator
+        }
+        _ => GarbageGenerator::new(SURVIVAL_HOLE_CHANGE_PROBABILITY),
+    };
+    context.update_garbage_rows(board.count_garbage_rows());
+
+    let timings = context.get_mode().get_timings();
+    let mut timers = FrameTimers::default();
+    let mut last_frame = Instant::now();
 
     loop {
-        if !board.has_piece() && !board.add_piece(context.get_piece()) {
-            break;
+        if !board.has_piece() && timers.spawn_delay == 0 {
+            let Some(piece) = context.get_piece() else {
+                break;
+            };
+            if !board.add_piece(piece.clone()) {
+                if context.get_mode() == GameMode::Zen {
+                    // there is no game over in zen mode, the board is simply cleared
+                    board = Board::new();
+                    board.add_piece(piece);
+                } else if let Some(next_attempt) = context.fail_attempt() {
+                    // a failed perfect clear attempt starts over
+                    board = next_attempt;
+                    continue;
+                } else {
+                    context.top_out(&mut board);
+                    break;
+                }
+            }
+            context.piece_spawned(&mut board);
+            timers.lock_frames = 0;
         }
 
-        context.print_game(format!("{board}"))?;
+        if context.handle_signals()? {
+            break;
+        }
+        if context.is_render_due() {
+            context.print_game(&board)?;
+        }
 
-        if poll(Duration::from_millis(1))? {
+        // sleeping until the next frame, unless a key wakes the game up
+        if poll(context.get_idle_time(last_frame + FRAME_DURATION))? {
             let event = read()?;
+            if context.is_paused() {
+                match context.pause_menu_event(&mut board, event)? {
+                    Some(Item::Restart) => return Ok(true),
+                    Some(Item::Quit) => break,
+                    _ => continue,
+                }
+            }
+            let had_piece = board.has_piece();
 
-            let score = if event == Event::Key(KeyCode::Esc.into()) {
-                break;
+            // the keys don't move the pieces played by the computer, nor
+            // those out of sight
+            let input = if context.has_bot() || context.is_too_small() {
+                None
             } else if event == Event::Key(KeyCode::Left.into()) {
-                board.move_piece(Direction::Left).1
+                Some(Input::Left)
             } else if event == Event::Key(KeyCode::Right.into()) {
-                board.move_piece(Direction::Right).1
+                Some(Input::Right)
             } else if event == Event::Key(KeyCode::Down.into()) {
-                board.move_piece(Direction::Down).1
+                Some(Input::SoftDrop)
             } else if event == Event::Key(KeyCode::Char('z').into())
                 || event == Event::Key(KeyCode::Char('Z').into())
             {
-                board.rotate_piece(utils::Rotation::CounterClockwise);
-                Score::default()
+                Some(Input::RotateCounterClockwise)
             } else if event == Event::Key(KeyCode::Char('x').into())
                 || event == Event::Key(KeyCode::Char('X').into())
             {
-                board.rotate_piece(utils::Rotation::Clockwise);
+                Some(Input::RotateClockwise)
+            } else if event == Event::Key(KeyCode::Char(' ').into()) {
+                Some(Input::HardDrop)
+            } else {
+                None
+            };
+
+            let score = if let Some(input) = input {
+                context.record_input(input);
+                apply_input(context, &mut board, input)
+            } else if let Event::Resize(width, height) = event {
+                context.resize(width, height);
+                Score::default()
+            } else if event == Event::FocusLost {
+                context.pause();
+                Score::default()
+            } else if event == Event::Key(KeyEvent::new(KeyCode::Char('z'), KeyModifiers::CONTROL))
+            {
+                context.suspend()?;
                 Score::default()
+            } else if event == Event::Key(KeyCode::Esc.into()) {
+                break;
             } else if event == Event::Key(KeyCode::Char('c').into())
                 || event == Event::Key(KeyCode::Char('C').into())
             {
-                paused = !paused;
+                context.pause();
                 Score::default()
             } else if event == Event::Key(KeyCode::Char('m').into())
                 || event == Event::Key(KeyCode::Char('M').into())
             {
                 context.mute_toggle();
                 Score::default()
-            } else if event == Event::Key(KeyCode::Char(' ').into()) {
-                board.land_piece()
+            } else if event == Event::Key(KeyCode::Char('u').into())
+                || event == Event::Key(KeyCode::Char('U').into())
+            {
+                context.undo(&mut board);
+                Score::default()
+            } else if event == Event::Key(KeyCode::Char('n').into())
+                || event == Event::Key(KeyCode::Char('N').into())
+            {
+                context.swap_next_piece(&mut board);
+                Score::default()
+            } else if event == Event::Key(KeyCode::Char('r').into())
+                || event == Event::Key(KeyCode::Char('R').into())
+            {
+                context.announce_heights(&board);
+                Score::default()
+            } else if event == Event::Key(KeyCode::Char('s').into())
+                || event == Event::Key(KeyCode::Char('S').into())
+            {
+                context.stats_toggle();
+                Score::default()
+            } else if event == Event::Key(KeyCode::Char('g').into())
+                || event == Event::Key(KeyCode::Char('G').into())
+            {
+                context.gravity_toggle();
+                Score::default()
+            } else if event == Event::Key(KeyCode::Char('h').into())
+                || event == Event::Key(KeyCode::Char('H').into())
+            {
+                context.hint_toggle(&mut board);
+                Score::default()
             } else if event == Event::Key(KeyCode::Char('-').into()) {
                 context.volume_down();
                 Score::default()
@@ -76,19 +346,171 @@ fn game_loop(context: &mut Context) -> std::io::Result<()> {
                 Score::default()
             };
 
-            context.increment_score(score);
+            if had_piece && !board.has_piece() {
+                piece_locked(context, &mut timers, &timings, score, &mut board);
+            }
+        }
+
+        // the game can't go on out of sight
+        if context.is_too_small() {
+            context.pause();
+        }
+        let paused = context.is_paused();
+        if !paused && let Some(input) = context.bot_input(&board) {
+            let had_piece = board.has_piece();
+            context.record_bot_input(input);
+            let score = apply_input(context, &mut board, input);
+
+            if had_piece && !board.has_piece() {
+                piece_locked(context, &mut timers, &timings, score, &mut board);
+            }
+        }
+
+        context.update_garbage_rows(board.count_garbage_rows());
+        if context.is_finished() {
+            context.print_game(&board)?;
+            break;
         }
 
         if paused {
+            last_frame = Instant::now();
             continue;
         }
 
-        if (now.elapsed().as_micros() as f64) >= (PIECE_DROP_MICROSECONDS * speed_micros) {
-            context.increment_score(board.move_piece(Direction::Down).1);
-            speed_micros = context.get_game_speed_micros();
-            now = Instant::now();
+        let rising = context.take_rising_garbage();
+        if rising > 0 && !board.insert_garbage(rising, generator.next_hole()) {
+            context.top_out(&mut board);
+            break;
+        }
+
+        while last_frame.elapsed() >= FRAME_DURATION {
+            last_frame += FRAME_DURATION;
+            step_frame(context, &mut board, &mut timers, &timings);
         }
     }
 
-    Ok(())
+    finish_animations(context, &mut board)?;
+
+    Ok(false)
+}
+
+/// Lets the last animations play once the game is over, unless skipped with
+/// Esc.
+fn finish_animations(context: &mut Context, board: &mut Board) -> std::io::Result<()> {
+    context.observe(board);
+    while context.is_animating() {
+        if context.handle_signals()? {
+            break;
+        }
+        // the game is over already, there is nothing left to pause
+        context.resume();
+        if context.is_render_due() {
+            context.print_game(board)?;
+        }
+
+        if poll(context.get_idle_time(Instant::now() + FRAME_DURATION))? {
+            match read()? {
+                Event::Resize(width, height) => context.resize(width, height),
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                }) => break,
+                _ => {}
+            }
+        }
+    }
+
+    context.print_game(board)
+}
+
+fn step_frame(
+    context: &mut Context,
+    board: &mut Board,
+    timers: &mut FrameTimers,
+    timings: &Timings,
+) {
+    if let Some(score) = timers.step(board, context.get_gravity(), timings) {
+        piece_locked(context, timers, timings, score, board);
+    }
+}
+
+/// Plays an input on the active piece, and tells right away what it did.
+fn apply_input(context: &mut Context, board: &mut Board, input: Input) -> Score {
+    let score = board.apply_input(input);
+    // every move is told as it's made, rather than all at once on the lock
+    context.observe(board);
+
+    score
+}
+
+fn piece_locked(
+    context: &mut Context,
+    timers: &mut FrameTimers,
+    timings: &Timings,
+    score: Score,
+    board: &mut Board,
+) {
+    // the drills and the trainings may start over on a new board
+    context.observe(board);
+    timers.piece_locked(timings, &score);
+
+    context.increment_score(score);
+    context.check_goal(board.get_last_lock());
+    context.check_finesse(board);
+    context.check_attempt(board);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Write, rc::Rc};
+
+    use super::*;
+    use crate::{
+        pieces::{Piece, Tetromino},
+        render::View,
+    };
+
+    /// Output shared with the narrator owned by the context.
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tells_every_move_before_the_lock() {
+        let out = Shared::default();
+        let mut context = Context::new(GameMode::Marathon);
+        context.set_renderer(Box::new(NarrationRenderer::with_writer(out.clone())));
+        let mut board = Board::from_text("XXXX..XXXX").unwrap();
+        board.add_piece(Piece::new(Tetromino::O, Tetromino::O.get_spawn_position()));
+        board.take_events();
+
+        let timings = GameMode::Marathon.get_timings();
+        let mut timers = FrameTimers::default();
+        let told = [
+            "column 4, spawn orientation\r\n",
+            "column 5, spawn orientation\r\n",
+            "O locked, 1 line cleared\r\n",
+        ];
+        for (input, line) in [Input::Left, Input::Right, Input::HardDrop]
+            .into_iter()
+            .zip(told)
+        {
+            let score = apply_input(&mut context, &mut board, input);
+            if !board.has_piece() {
+                piece_locked(&mut context, &mut timers, &timings, score, &mut board);
+            }
+            context.render(&View::default()).unwrap();
+
+            let written = String::from_utf8(out.0.borrow().clone()).unwrap();
+            assert!(written.ends_with(line), "{written:?}");
+        }
+    }
 }
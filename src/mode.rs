@@ -0,0 +1,298 @@
+use std::{str::FromStr, time::Duration};
+
+use crate::{
+    board::{Board, BOARD_HEIGHT},
+    utils::{Direction, Score},
+};
+
+pub const DIG_ROWS_DEFAULT: usize = 10;
+pub const DIG_ROWS_MAX: usize = BOARD_HEIGHT - 4;
+
+pub const SURVIVAL_RISE_INTERVAL: Duration = Duration::from_secs(10);
+pub const SURVIVAL_RISE_INTERVAL_MIN: Duration = Duration::from_millis(1500);
+pub const SURVIVAL_RISE_ACCELERATION: f64 = 0.92;
+pub const SURVIVAL_HOLE_CHANGE_PROBABILITY: f64 = 0.3;
+
+pub const VERSUS_ROUNDS_DEFAULT: usize = 3;
+
+pub const MASTER_LEVEL_MAX: u32 = 999;
+pub const MASTER_SECTION_LEVELS: u32 = 100;
+
+/// Gravity of the master mode, as (starting level, 1/256th of rows per frame).
+const MASTER_GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+/// Points needed for each of the master mode grades.
+const MASTER_GRADES: [(u64, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1_400, "6"),
+    (2_000, "5"),
+    (3_500, "4"),
+    (5_500, "3"),
+    (8_000, "2"),
+    (12_000, "1"),
+    (16_000, "S1"),
+    (22_000, "S2"),
+    (30_000, "S3"),
+    (40_000, "S4"),
+    (52_000, "S5"),
+    (66_000, "S6"),
+    (82_000, "S7"),
+    (100_000, "S8"),
+    (120_000, "S9"),
+];
+
+/// Delays, in frames, applied by a game mode between the moments a piece
+/// touches the stack, locks, and the next piece enters the board.
+pub struct Timings {
+    /// Entry delay, between locking a piece and spawning the next one.
+    pub are: u32,
+    /// Extra entry delay, whenever the locked piece completed some lines.
+    pub line_clear_delay: u32,
+    /// Time a grounded piece may still move, or none for locking on the
+    /// first failed gravity drop.
+    pub lock_delay: Option<u32>,
+}
+
+/// Frame counters driving the gravity, the lock delay and the entry delay.
+#[derive(Default)]
+pub struct FrameTimers {
+    /// Accumulated gravity, in rows. The piece drops once it reaches a full row.
+    pub gravity: f64,
+    pub lock_frames: u32,
+    pub spawn_delay: u32,
+}
+
+impl FrameTimers {
+    /// Advances the active piece by one frame with the given gravity, in rows
+    /// per frame, and returns the score of the lock whenever the piece locked.
+    pub fn step(&mut self, board: &mut Board, gravity: f64, timings: &Timings) -> Option<Score> {
+        if self.spawn_delay > 0 {
+            self.spawn_delay -= 1;
+            return None;
+        }
+        if !board.has_piece() {
+            return None;
+        }
+
+        // with high gravity the piece can drop several rows within the same frame
+        self.gravity += gravity;
+        while self.gravity >= 1.0 {
+            self.gravity -= 1.0;
+
+            if timings.lock_delay.is_some() {
+                if board.drop_piece() {
+                    self.lock_frames = 0;
+                } else {
+                    self.gravity = 0.0;
+                }
+            } else {
+                let (moved, score) = board.move_piece(Direction::Down);
+                if !moved {
+                    return Some(score);
+                }
+            }
+        }
+
+        if let Some(lock_delay) = timings.lock_delay
+            && board.is_piece_grounded()
+        {
+            self.lock_frames += 1;
+            if self.lock_frames >= lock_delay {
+                return Some(board.lock_piece());
+            }
+        }
+
+        None
+    }
+
+    /// Starts the entry delay of the next piece, once a piece locked.
+    pub fn piece_locked(&mut self, timings: &Timings, score: &Score) {
+        self.gravity = 0.0;
+        self.spawn_delay = timings.are;
+        if score.lines_destroyed > 0 {
+            self.spawn_delay += timings.line_clear_delay;
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DigStyle {
+    Clean,
+    Messy,
+    Custom(f64),
+}
+
+impl DigStyle {
+    pub fn hole_change_probability(&self) -> f64 {
+        match self {
+            Self::Clean => 0.0,
+            Self::Messy => 1.0,
+            Self::Custom(probability) => *probability,
+        }
+    }
+}
+
+impl FromStr for DigStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clean" => Ok(Self::Clean),
+            "messy" => Ok(Self::Messy),
+            _ => match s.parse::<f64>() {
+                Ok(p) if (0.0..=1.0).contains(&p) => Ok(Self::Custom(p)),
+                _ => Err(format!(
+                    "invalid dig style '{s}', expected 'clean', 'messy' or a probability between 0 and 1"
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GameMode {
+    #[default]
+    Marathon,
+    Dig {
+        rows: usize,
+        style: DigStyle,
+    },
+    Survival,
+    Master,
+    Zen,
+    Puzzle {
+        challenge: Option<usize>,
+    },
+    PerfectClear,
+    Drill,
+    Versus {
+        rounds: usize,
+    },
+    Demo,
+}
+
+impl GameMode {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Marathon => "MARATHON",
+            Self::Dig { .. } => "DIG",
+            Self::Survival => "SURVIVAL",
+            Self::Master => "MASTER",
+            Self::Zen => "ZEN",
+            Self::Puzzle { .. } => "PUZZLE",
+            Self::PerfectClear => "PC TRAINING",
+            Self::Drill => "FINESSE DRILL",
+            Self::Versus { .. } => "VERSUS",
+            Self::Demo => "DEMO",
+        }
+    }
+
+    pub fn get_timings(&self) -> Timings {
+        match self {
+            Self::Master => Timings {
+                are: 30,
+                line_clear_delay: 41,
+                lock_delay: Some(30),
+            },
+            _ => Timings {
+                are: 0,
+                line_clear_delay: 0,
+                lock_delay: None,
+            },
+        }
+    }
+}
+
+/// Returns the master mode gravity for the given level, in rows per frame.
+pub fn master_gravity(level: u32) -> f64 {
+    let internal = MASTER_GRAVITY
+        .iter()
+        .rev()
+        .find(|(from, _)| level >= *from)
+        .map_or(4, |(_, gravity)| *gravity);
+
+    internal as f64 / 256.0
+}
+
+pub fn master_grade(points: u64) -> &'static str {
+    MASTER_GRADES
+        .iter()
+        .rev()
+        .find(|(needed, _)| points >= *needed)
+        .map_or("9", |(_, grade)| grade)
+}
+
+/// Level at which the current master mode section ends, as far as new pieces
+/// take the level. The level only moves past the section end by clearing
+/// lines, and never past the last level.
+pub fn master_section_end(level: u32) -> u32 {
+    ((level / MASTER_SECTION_LEVELS + 1) * MASTER_SECTION_LEVELS - 1).min(MASTER_LEVEL_MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn master_gravity_by_level() {
+        assert_eq!(4.0 / 256.0, master_gravity(0));
+        assert_eq!(4.0 / 256.0, master_gravity(29));
+        assert_eq!(6.0 / 256.0, master_gravity(30));
+        // the gravity drops back at level 200, before reaching 20G
+        assert_eq!(4.0 / 256.0, master_gravity(200));
+        assert_eq!(1.0, master_gravity(251));
+        assert_eq!(20.0, master_gravity(500));
+        assert_eq!(20.0, master_gravity(MASTER_LEVEL_MAX));
+    }
+
+    #[test]
+    fn master_grade_by_points() {
+        assert_eq!("9", master_grade(0));
+        assert_eq!("9", master_grade(399));
+        assert_eq!("8", master_grade(400));
+        assert_eq!("S1", master_grade(16_000));
+        assert_eq!("S9", master_grade(1_000_000));
+    }
+
+    #[test]
+    fn master_section_end_by_level() {
+        assert_eq!(99, master_section_end(0));
+        assert_eq!(99, master_section_end(99));
+        assert_eq!(199, master_section_end(100));
+        assert_eq!(MASTER_LEVEL_MAX, master_section_end(950));
+        assert_eq!(MASTER_LEVEL_MAX, master_section_end(MASTER_LEVEL_MAX));
+    }
+}
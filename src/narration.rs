@@ -0,0 +1,155 @@
+use std::io::{Stdout, Write};
+
+use crate::{
+    board::GameEvent,
+    render::{Renderer, View},
+    utils::Direction,
+};
+
+/// Tells the game as lines of plain text, one after the other, for the screen
+/// readers to follow. Nothing gets drawn: the lines come from the events of
+/// the board, and from what the game announces.
+pub struct NarrationRenderer<W: Write = Stdout> {
+    out: W,
+    pending: Vec<String>,
+}
+
+impl NarrationRenderer {
+    pub fn new() -> Self {
+        Self::with_writer(std::io::stdout())
+    }
+}
+
+impl<W: Write> NarrationRenderer<W> {
+    pub fn with_writer(out: W) -> Self {
+        Self {
+            out,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<W: Write> Renderer for NarrationRenderer<W> {
+    /// Writes the lines told since the last view, the view itself is left out.
+    fn render(&mut self, _view: &View) -> std::io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+
+        // the terminal is in raw mode, every line returns to the first column
+        for line in self.pending.drain(..) {
+            write!(self.out, "{line}\r\n")?;
+        }
+        self.out.flush()
+    }
+
+    fn observe(&mut self, event: &GameEvent) {
+        if let Some(line) = describe(event) {
+            self.pending.push(line);
+        }
+    }
+
+    fn announce(&mut self, text: &str) {
+        self.pending.push(text.to_string());
+    }
+
+    fn is_linear(&self) -> bool {
+        true
+    }
+}
+
+/// Returns the line telling what happened on the board, if it's worth telling.
+/// The columns are counted from 1, on the left.
+pub fn describe(event: &GameEvent) -> Option<String> {
+    match event {
+        GameEvent::PieceSpawned { tetromino, column } => Some(format!(
+            "{} piece, column {}",
+            tetromino.to_char(),
+            column + 1
+        )),
+        GameEvent::PieceMoved {
+            column,
+            orientation,
+        } => Some(format!(
+            "column {}, {}",
+            column + 1,
+            describe_orientation(*orientation)
+        )),
+        GameEvent::PieceLocked { tetromino, lock } => {
+            let mut line = format!("{} locked", tetromino.to_char());
+            if lock.tspin {
+                line.push_str(", T-spin");
+            }
+            match lock.lines {
+                0 => {}
+                1 => line.push_str(", 1 line cleared"),
+                lines => line.push_str(&format!(", {lines} lines cleared")),
+            }
+            if lock.perfect_clear {
+                line.push_str(", perfect clear");
+            }
+
+            Some(line)
+        }
+        // the lock tells the lines it cleared, and the drop ends in a lock
+        GameEvent::HardDropped { .. } | GameEvent::LinesCleared { .. } => None,
+    }
+}
+
+/// Returns the line telling the height of every column, from left to right.
+pub fn describe_heights(heights: &[usize]) -> String {
+    let heights = heights
+        .iter()
+        .map(|height| height.to_string())
+        .collect::<Vec<String>>();
+
+    format!("column heights: {}", heights.join(", "))
+}
+
+// Private functions
+
+fn describe_orientation(orientation: Direction) -> &'static str {
+    match orientation {
+        Direction::Up => "spawn orientation",
+        Direction::Right => "rotated right",
+        Direction::Down => "upside down",
+        Direction::Left => "rotated left",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::Board,
+        pieces::{Piece, Tetromino},
+        utils::Input,
+    };
+
+    #[test]
+    fn tells_the_game() {
+        let mut board = Board::from_text("XXXX..XXXX").unwrap();
+        board.add_piece(Piece::new(Tetromino::O, Tetromino::O.get_spawn_position()));
+        board.apply_input(Input::RotateClockwise);
+        board.apply_input(Input::Left);
+        board.apply_input(Input::Right);
+        board.apply_input(Input::HardDrop);
+
+        let mut renderer = NarrationRenderer::with_writer(Vec::new());
+        for event in board.take_events() {
+            renderer.observe(&event);
+        }
+        renderer.announce(&describe_heights(&board.get_column_heights()));
+        renderer.render(&View::default()).unwrap();
+
+        assert_eq!(
+            "O piece, column 5\r\n\
+             column 5, rotated right\r\n\
+             column 4, rotated right\r\n\
+             column 5, rotated right\r\n\
+             O locked, 1 line cleared\r\n\
+             column heights: 0, 0, 0, 0, 1, 1, 0, 0, 0, 0\r\n",
+            String::from_utf8(renderer.out).unwrap()
+        );
+    }
+}
@@ -0,0 +1,615 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::{
+    board::{Board, BOARD_HEIGHT},
+    context::Context,
+    render::{self, Line, View},
+    utils::FRAME_DURATION,
+    versus::{Side, BOARD_COLUMNS, ONLINE_KEYMAP, ROUND_PAUSE, SIDE_COLUMNS},
+};
+
+/// Version of the protocol below, both players must speak the same one.
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 7420;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const COUNTDOWN_SECONDS: u64 = 3;
+/// Bytes the opponent may leave unread, a few hundred boards, before the
+/// connection is given up on.
+const OUTGOING_MAX: usize = 64 * 1024;
+
+/// How this instance takes part in a networked match.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Network {
+    Host(String),
+    Join(String),
+}
+
+/// Messages exchanged by the players, one per line of text:
+///
+/// ```text
+/// HELLO textris 1
+/// START 3
+/// BOARD ........../..........(20 rows)
+/// GARBAGE 2
+/// LOST
+/// BYE
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Hello {
+        version: u32,
+    },
+    /// Sent by the host once both players are there, with the match length.
+    Start {
+        rounds: usize,
+    },
+    /// The board of the sender, active piece included, rows separated by '/'.
+    Board(String),
+    Garbage(usize),
+    /// The sender topped out, and lost the current round.
+    Lost,
+    Bye,
+}
+
+impl Message {
+    pub fn to_line(&self) -> String {
+        match self {
+            Self::Hello { version } => format!("HELLO textris {version}\n"),
+            Self::Start { rounds } => format!("START {rounds}\n"),
+            Self::Board(rows) => format!("BOARD {rows}\n"),
+            Self::Garbage(rows) => format!("GARBAGE {rows}\n"),
+            Self::Lost => String::from("LOST\n"),
+            Self::Bye => String::from("BYE\n"),
+        }
+    }
+}
+
+impl FromStr for Message {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<&str>>();
+        let number = |value: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("invalid message '{s}'"))
+        };
+
+        match words.as_slice() {
+            ["HELLO", "textris", version] => Ok(Self::Hello {
+                version: version
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid message '{s}'"))?,
+            }),
+            ["START", rounds] => Ok(Self::Start {
+                rounds: number(rounds)?,
+            }),
+            ["BOARD", rows] => Ok(Self::Board(rows.to_string())),
+            // no attack can send more rows than the board holds
+            ["GARBAGE", rows] => Ok(Self::Garbage(number(rows)?.min(BOARD_HEIGHT))),
+            ["LOST"] => Ok(Self::Lost),
+            ["BYE"] => Ok(Self::Bye),
+            _ => Err(format!("invalid message '{s}'")),
+        }
+    }
+}
+
+/// A connection to the other player. Once the match started, neither sending
+/// nor receiving blocks the game loop: what the socket doesn't take right away
+/// waits in the outgoing buffer for the next flush.
+pub struct Connection {
+    buffer: Vec<u8>,
+    outgoing: Vec<u8>,
+    stream: TcpStream,
+}
+
+impl Connection {
+    /// Waits for an opponent on the given address, and starts a match of the
+    /// given number of rounds with them.
+    pub fn host(address: &str, rounds: usize) -> Result<Self, String> {
+        let listener = TcpListener::bind(with_default_port(address))
+            .map_err(|e| format!("cannot listen on '{address}': {e}"))?;
+
+        Self::accept(&listener, rounds, PROTOCOL_VERSION)
+    }
+
+    /// Joins the match hosted on the given address, and returns the connection
+    /// along with the number of rounds chosen by the host.
+    pub fn join(address: &str) -> Result<(Self, usize), String> {
+        let address = with_default_port(address);
+        let socket_address = address
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| format!("invalid address '{address}'"))?;
+        let stream = TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT)
+            .map_err(|e| format!("cannot connect to '{address}': {e}"))?;
+
+        let mut connection = Self::new(stream)?;
+        connection.handshake(PROTOCOL_VERSION)?;
+        match connection.receive_blocking()? {
+            Message::Start { rounds } if rounds > 0 => {
+                connection.start()?;
+                Ok((connection, rounds))
+            }
+            message => Err(format!("unexpected message from the host: {message:?}")),
+        }
+    }
+
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        self.outgoing
+            .extend_from_slice(message.to_line().as_bytes());
+        self.flush()
+    }
+
+    /// Writes as much of the outgoing buffer as the socket takes right now.
+    /// An opponent who stopped reading ends the connection, once the buffer
+    /// is full.
+    pub fn flush(&mut self) -> Result<(), String> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(String::from("the opponent disconnected")),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if self.outgoing.len() > OUTGOING_MAX {
+                        return Err(String::from(
+                            "connection lost: the opponent stopped reading",
+                        ));
+                    }
+                    return Ok(());
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("connection lost: {e}")),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the next message of the opponent, or none when nothing arrived.
+    pub fn receive(&mut self) -> Result<Option<Message>, String> {
+        if let Some(message) = self.take_message()? {
+            return Ok(Some(message));
+        }
+
+        let mut chunk = [0; 1024];
+        match self.stream.read(&mut chunk) {
+            Ok(0) => Err(String::from("the opponent disconnected")),
+            Ok(read) => {
+                self.buffer.extend_from_slice(&chunk[..read]);
+                self.take_message()
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                Ok(None)
+            }
+            Err(e) => Err(format!("connection lost: {e}")),
+        }
+    }
+}
+
+// Private functions
+impl Connection {
+    fn new(stream: TcpStream) -> Result<Self, String> {
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+            .and_then(|_| stream.set_nodelay(true))
+            .map_err(|e| format!("cannot set up the connection: {e}"))?;
+
+        Ok(Self {
+            buffer: Vec::new(),
+            outgoing: Vec::new(),
+            stream,
+        })
+    }
+
+    fn accept(listener: &TcpListener, rounds: usize, version: u32) -> Result<Self, String> {
+        let (stream, _) = listener
+            .accept()
+            .map_err(|e| format!("cannot accept the opponent: {e}"))?;
+
+        let mut connection = Self::new(stream)?;
+        connection.handshake(version)?;
+        connection.send(&Message::Start { rounds })?;
+        connection.start()?;
+
+        Ok(connection)
+    }
+
+    /// Both players introduce themselves, and make sure they speak the same
+    /// version of the protocol.
+    fn handshake(&mut self, version: u32) -> Result<(), String> {
+        self.send(&Message::Hello { version })?;
+
+        match self.receive_blocking()? {
+            Message::Hello { version: remote } if remote == version => Ok(()),
+            Message::Hello { version: remote } => {
+                let _ = self.send(&Message::Bye);
+                Err(format!(
+                    "version mismatch, this game speaks protocol {version} while the opponent speaks {remote}"
+                ))
+            }
+            message => Err(format!("unexpected message from the opponent: {message:?}")),
+        }
+    }
+
+    /// Switches to non blocking reads, for the game loop.
+    fn start(&mut self) -> Result<(), String> {
+        self.stream
+            .set_nonblocking(true)
+            .map_err(|e| format!("cannot set up the connection: {e}"))
+    }
+
+    fn receive_blocking(&mut self) -> Result<Message, String> {
+        let started = Instant::now();
+        while started.elapsed() < HANDSHAKE_TIMEOUT {
+            if let Some(message) = self.receive()? {
+                return Ok(message);
+            }
+        }
+
+        Err(String::from("the opponent is not answering"))
+    }
+
+    fn take_message(&mut self) -> Result<Option<Message>, String> {
+        let Some(end) = self.buffer.iter().position(|b| *b == b'\n') else {
+            return Ok(None);
+        };
+
+        let line = self.buffer.drain(..=end).collect::<Vec<u8>>();
+        String::from_utf8_lossy(&line).trim().parse().map(Some)
+    }
+}
+
+fn with_default_port(address: &str) -> String {
+    if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{DEFAULT_PORT}")
+    }
+}
+
+/// Progress of a round of a networked match.
+enum Round {
+    Countdown(Instant),
+    Playing,
+    /// The round is over, with whether each player topped out.
+    Ended {
+        at: Instant,
+        local_lost: bool,
+        remote_lost: bool,
+    },
+}
+
+/// Plays a networked match against the player at the other end of the
+/// connection. Each side plays its own board, and sends the other one board
+/// snapshots, garbage attacks and top outs. Returns the result of the match.
+pub fn game_loop(
+    context: &mut Context,
+    mut connection: Connection,
+    rounds: usize,
+) -> std::io::Result<Option<String>> {
+    let wins_needed = rounds / 2 + 1;
+    let mut local = Side::new(&ONLINE_KEYMAP);
+    let mut remote_board = Board::new();
+    let mut remote_wins = 0;
+    let mut last_sent = String::new();
+    let mut round = Round::Countdown(Instant::now());
+    let mut last_frame = Instant::now();
+
+    loop {
+        // messages of the opponent, after sending what's left of ours
+        let outcome = connection.flush().err().or_else(|| loop {
+            match connection.receive() {
+                Ok(Some(Message::Board(rows))) => {
+                    if let Ok(board) = Board::from_text(&rows.replace('/', "\n")) {
+                        remote_board = board;
+                    }
+                }
+                Ok(Some(Message::Garbage(rows))) => local.garbage.receive(rows),
+                Ok(Some(Message::Lost)) => match &mut round {
+                    Round::Ended { remote_lost, .. } => *remote_lost = true,
+                    _ => {
+                        round = Round::Ended {
+                            at: Instant::now(),
+                            local_lost: false,
+                            remote_lost: true,
+                        };
+                    }
+                },
+                Ok(Some(Message::Bye)) => break Some(String::from("the opponent left the match")),
+                Ok(Some(_)) => {}
+                Ok(None) => break None,
+                Err(e) => break Some(e),
+            }
+        });
+        if let Some(reason) = outcome {
+            return Ok(Some(format!(
+                "Match interrupted at {} - {remote_wins}: {reason}",
+                local.wins
+            )));
+        }
+
+        let status = match &round {
+            Round::Countdown(started) => {
+                let left = COUNTDOWN_SECONDS.saturating_sub(started.elapsed().as_secs());
+                if left == 0 {
+                    round = Round::Playing;
+                    last_frame = Instant::now();
+                }
+                format!("GET READY... {left}")
+            }
+            Round::Playing => {
+                local.spawn_piece();
+                String::new()
+            }
+            Round::Ended {
+                local_lost,
+                remote_lost,
+                ..
+            } => match (local_lost, remote_lost) {
+                (true, true) => String::from("DRAW, BOTH PLAYERS TOPPED OUT"),
+                (true, false) => String::from("YOU LOST THE ROUND"),
+                _ => String::from("YOU WON THE ROUND"),
+            },
+        };
+
+        if context.handle_signals()? {
+            let _ = connection.send(&Message::Bye);
+            return Ok(Some(format!(
+                "Match abandoned at {} - {remote_wins}",
+                local.wins
+            )));
+        }
+        if context.is_render_due() {
+            print_match(
+                context,
+                &local,
+                &remote_board,
+                remote_wins,
+                wins_needed,
+                &status,
+            )?;
+        }
+
+        if poll(context.get_idle_time(last_frame + FRAME_DURATION))? {
+            match read()? {
+                Event::Resize(width, height) => context.resize(width, height),
+                // there is no pause, the opponent keeps playing while suspended
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('z'),
+                    modifiers: KeyModifiers::CONTROL,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => context.suspend()?,
+                Event::Key(KeyEvent {
+                    code,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => match code {
+                    KeyCode::Esc => {
+                        let _ = connection.send(&Message::Bye);
+                        return Ok(Some(format!(
+                            "Match abandoned at {} - {remote_wins}",
+                            local.wins
+                        )));
+                    }
+                    KeyCode::Char('m') | KeyCode::Char('M') => context.mute_toggle(),
+                    KeyCode::Char('-') => context.volume_down(),
+                    KeyCode::Char('+') => context.volume_up(),
+                    _ => {
+                        if let Round::Playing = round
+                            && let Some(input) = local.keymap.get_input(code)
+                        {
+                            let attack = local.apply_input(input);
+                            if attack > 0 {
+                                let _ = connection.send(&Message::Garbage(attack));
+                            }
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        // there is no pause, the opponent keeps playing anyway: a suspend
+        // only keeps the frames stopped from being played all at once
+        if context.is_paused() {
+            context.resume();
+            last_frame = Instant::now();
+        }
+        if let Round::Playing = round {
+            while last_frame.elapsed() >= FRAME_DURATION {
+                last_frame += FRAME_DURATION;
+                let attack = local.step_frame();
+                if attack > 0 {
+                    let _ = connection.send(&Message::Garbage(attack));
+                }
+            }
+
+            if local.topped_out {
+                let _ = connection.send(&Message::Lost);
+                round = Round::Ended {
+                    at: Instant::now(),
+                    local_lost: true,
+                    remote_lost: false,
+                };
+            }
+        }
+
+        let snapshot = local.board.to_text_with_piece();
+        if snapshot != last_sent {
+            let rows = snapshot.lines().collect::<Vec<&str>>().join("/");
+            if let Err(e) = connection.send(&Message::Board(rows)) {
+                return Ok(Some(format!(
+                    "Match interrupted at {} - {remote_wins}: {e}",
+                    local.wins
+                )));
+            }
+            last_sent = snapshot;
+        }
+
+        if let Round::Ended {
+            at,
+            local_lost,
+            remote_lost,
+        } = round
+            && at.elapsed() >= ROUND_PAUSE
+        {
+            local.wins += usize::from(remote_lost && !local_lost);
+            remote_wins += usize::from(local_lost && !remote_lost);
+
+            if local.wins >= wins_needed {
+                let _ = connection.send(&Message::Bye);
+                return Ok(Some(format!(
+                    "You win the match {} - {remote_wins}",
+                    local.wins
+                )));
+            }
+            if remote_wins >= wins_needed {
+                let _ = connection.send(&Message::Bye);
+                return Ok(Some(format!(
+                    "You lose the match {} - {remote_wins}",
+                    local.wins
+                )));
+            }
+
+            local.reset();
+            remote_board = Board::new();
+            round = Round::Countdown(Instant::now());
+        }
+    }
+}
+
+fn print_match(
+    context: &mut Context,
+    local: &Side,
+    remote_board: &Board,
+    remote_wins: usize,
+    wins_needed: usize,
+    status: &str,
+) -> std::io::Result<()> {
+    let mut local_board = local.board_lines();
+    local_board.extend([Line::default(), Line::from(status)]);
+
+    let columns = vec![
+        (0, local_board),
+        (BOARD_COLUMNS, local.panel("YOU", wins_needed)),
+        (SIDE_COLUMNS, render::board_lines(remote_board)),
+        (
+            SIDE_COLUMNS + BOARD_COLUMNS,
+            vec![
+                Line::from("OPPONENT"),
+                Line::from(format!("WINS:     {remote_wins} / {wins_needed}")),
+            ],
+        ),
+    ];
+
+    context.render(&View { columns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connect(
+        host_version: u32,
+    ) -> (
+        Result<Connection, String>,
+        Result<(Connection, usize), String>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let host = std::thread::spawn(move || Connection::accept(&listener, 3, host_version));
+        let guest = Connection::join(&address);
+
+        (host.join().unwrap(), guest)
+    }
+
+    fn receive_soon(connection: &mut Connection) -> Result<Option<Message>, String> {
+        let started = Instant::now();
+        loop {
+            match connection.receive() {
+                Ok(None) if started.elapsed() < Duration::from_secs(5) => {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                result => return result,
+            }
+        }
+    }
+
+    #[test]
+    fn message_round_trip() {
+        let messages = [
+            Message::Hello { version: 1 },
+            Message::Start { rounds: 5 },
+            Message::Board(String::from("........../XXXXXXXX.X")),
+            Message::Garbage(4),
+            Message::Lost,
+            Message::Bye,
+        ];
+
+        for message in messages {
+            assert_eq!(Ok(message.clone()), message.to_line().trim().parse());
+        }
+        assert!("GARBAGE lots".parse::<Message>().is_err());
+        assert!("HELLO tetris 1".parse::<Message>().is_err());
+        assert!("HELLO textris 4294967297".parse::<Message>().is_err());
+        assert_eq!(
+            Ok(Message::Garbage(BOARD_HEIGHT)),
+            "GARBAGE 18446744073709551615".parse()
+        );
+    }
+
+    #[test]
+    fn play_over_localhost() {
+        let (host, guest) = connect(PROTOCOL_VERSION);
+        let mut host = host.unwrap();
+        let (mut guest, rounds) = guest.unwrap();
+        assert_eq!(3, rounds);
+
+        guest.send(&Message::Garbage(2)).unwrap();
+        guest.send(&Message::Lost).unwrap();
+        assert_eq!(Ok(Some(Message::Garbage(2))), receive_soon(&mut host));
+        assert_eq!(Ok(Some(Message::Lost)), receive_soon(&mut host));
+        assert_eq!(Ok(None), host.receive());
+
+        drop(guest);
+        assert!(receive_soon(&mut host).is_err());
+    }
+
+    #[test]
+    fn opponent_not_reading() {
+        let (host, guest) = connect(PROTOCOL_VERSION);
+        let mut host = host.unwrap();
+        let (_guest, _) = guest.unwrap();
+
+        // the guest never reads, so the boards pile up until the host gives up
+        let board = Message::Board(vec![".........."; BOARD_HEIGHT].join("/"));
+        let sent = (0..100_000)
+            .take_while(|_| host.send(&board).is_ok())
+            .count();
+        assert!(sent < 100_000);
+        assert!(host.outgoing.len() <= OUTGOING_MAX + board.to_line().len());
+        assert!(host.flush().is_err_and(|e| e.contains("stopped reading")));
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let (host, guest) = connect(PROTOCOL_VERSION + 1);
+
+        assert!(host.is_err_and(|e| e.contains("version mismatch")));
+        assert!(guest.is_err_and(|e| e.contains("version mismatch")));
+    }
+}
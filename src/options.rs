@@ -0,0 +1,219 @@
+use crate::{
+    bot::{BotLevel, Weights},
+    mode::{DigStyle, GameMode, DIG_ROWS_DEFAULT, DIG_ROWS_MAX, VERSUS_ROUNDS_DEFAULT},
+    net::Network,
+    render::Style,
+    training::{BagOrder, OPENER_DEFAULT},
+};
+
+pub const USAGE: &str = "\
+Usage: textris [OPTIONS]
+
+Options:
+    --mode <MODE>           marathon (default), dig, survival, master, zen,
+                            puzzle, pc (perfect clear training), drill
+                            (finesse drill), versus (two players) or demo
+                            (the computer plays by itself)
+    --garbage-rows <N>      dig mode: number of garbage rows (1-16, default 10)
+    --dig-style <STYLE>     dig mode: clean, messy (default) or the probability
+                            (0 to 1) that the hole changes between rows
+    --challenge <N>         puzzle mode: challenge to play (default: the latest
+                            unlocked one)
+    --opener <NAME>         pc mode: field to start every attempt from, one of
+                            the openers in res/openers (default: empty)
+    --bag <PIECES>          pc mode: pieces dealt on every attempt, either
+                            'opener' (default), 'random' or a sequence like TIOL
+    --rounds <N>            versus mode: the match is best of N rounds
+                            (default 3)
+    --host <ADDRESS>        versus mode: wait for an opponent on the network,
+                            e.g. 0.0.0.0 (port 7420 unless given)
+    --join <ADDRESS>        versus mode: join the match hosted at the address
+    --ai <LEVEL>            versus mode: the right player is the computer;
+                            demo mode: the level of the computer, one of
+                            easy, medium (default), hard or max
+    --ai-weights <WEIGHTS>  weights of the computer's evaluation, e.g.
+                            height=-0.51,holes=-0.36,bumpiness=-0.18,
+                            wells=-0.1,lines=0.76
+    --bot <COMMAND>         an external bot speaking the Tetris Bot Protocol
+                            plays instead, at the pace of --ai: the whole
+                            game, or the right player in versus mode
+    --style <STYLE>         how the board looks: auto (default, guessed from
+                            the terminal), emoji, ascii, box, or colored
+                            blocks with ansi or truecolor
+    --theme <THEME>         colors and patterns of the pieces: one of the themes
+                            in res/themes (default: textris), or a theme file
+    --env                   run headless as a training environment, reading
+                            JSON requests on stdin (see the README)
+    --finesse               count the finesse faults, in any mode
+    --stats                 show the statistics panel (S toggles it)
+    --narrate               tell the game as lines of text, for screen readers
+                            (R tells the column heights)
+    -h, --help              print this help";
+
+pub struct Options {
+    pub ai: Option<BotLevel>,
+    pub ai_weights: Weights,
+    pub bag: BagOrder,
+    pub bot: Option<String>,
+    pub env: bool,
+    pub finesse: bool,
+    pub help: bool,
+    pub mode: GameMode,
+    pub narrate: bool,
+    pub network: Option<Network>,
+    pub opener: String,
+    pub stats: bool,
+    pub style: Style,
+    pub theme: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            ai: None,
+            ai_weights: Weights::default(),
+            bag: BagOrder::Opener,
+            bot: None,
+            env: false,
+            finesse: false,
+            help: false,
+            mode: GameMode::default(),
+            narrate: false,
+            network: None,
+            opener: String::from(OPENER_DEFAULT),
+            stats: false,
+            style: Style::detect(),
+            theme: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::default();
+        let mut mode = String::from("marathon");
+        let mut garbage_rows = DIG_ROWS_DEFAULT;
+        let mut dig_style = DigStyle::Messy;
+        let mut challenge = None;
+        let mut rounds = VERSUS_ROUNDS_DEFAULT;
+        let mut style = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => options.help = true,
+                "--finesse" => options.finesse = true,
+                "--stats" => options.stats = true,
+                "--narrate" => options.narrate = true,
+                "--env" => options.env = true,
+                "--style" => style = Some(Self::value_of(&arg, args.next())?.parse()?),
+                "--theme" => options.theme = Some(Self::value_of(&arg, args.next())?),
+                "--ai" => options.ai = Some(Self::value_of(&arg, args.next())?.parse()?),
+                "--bot" => options.bot = Some(Self::value_of(&arg, args.next())?),
+                "--ai-weights" => {
+                    options.ai_weights = Self::value_of(&arg, args.next())?.parse()?
+                }
+                "--mode" => mode = Self::value_of(&arg, args.next())?,
+                "--garbage-rows" => {
+                    let value = Self::value_of(&arg, args.next())?;
+                    garbage_rows = match value.parse::<usize>() {
+                        Ok(rows) if (1..=DIG_ROWS_MAX).contains(&rows) => rows,
+                        _ => return Err(format!("invalid number of garbage rows '{value}'")),
+                    };
+                }
+                "--challenge" => {
+                    let value = Self::value_of(&arg, args.next())?;
+                    challenge = match value.parse::<usize>() {
+                        Ok(number) if number > 0 => Some(number),
+                        _ => return Err(format!("invalid challenge '{value}'")),
+                    };
+                }
+                "--rounds" => {
+                    let value = Self::value_of(&arg, args.next())?;
+                    rounds = match value.parse::<usize>() {
+                        Ok(rounds) if rounds > 0 => rounds,
+                        _ => return Err(format!("invalid number of rounds '{value}'")),
+                    };
+                }
+                "--host" => {
+                    options.network = Some(Network::Host(Self::value_of(&arg, args.next())?))
+                }
+                "--join" => {
+                    options.network = Some(Network::Join(Self::value_of(&arg, args.next())?))
+                }
+                "--opener" => options.opener = Self::value_of(&arg, args.next())?,
+                "--bag" => options.bag = Self::value_of(&arg, args.next())?.parse()?,
+                "--dig-style" => dig_style = Self::value_of(&arg, args.next())?.parse()?,
+                _ => return Err(format!("unknown option '{arg}'")),
+            }
+        }
+
+        // the emoji have their own colors, a theme needs colored blocks
+        if matches!(style, Some(Style::Emoji)) && options.theme.is_some() {
+            return Err(String::from(
+                "--theme is not available with the emoji style",
+            ));
+        }
+        options.style = match style {
+            Some(style) => style,
+            None if options.theme.is_some() => Style::detect_colored(),
+            None => Style::detect(),
+        };
+
+        // networked matches are always versus matches
+        if options.network.is_some() && mode == "marathon" {
+            mode = String::from("versus");
+        }
+
+        options.mode = match mode.as_str() {
+            "marathon" => GameMode::Marathon,
+            "dig" => GameMode::Dig {
+                rows: garbage_rows,
+                style: dig_style,
+            },
+            "survival" => GameMode::Survival,
+            "master" => GameMode::Master,
+            "zen" => GameMode::Zen,
+            "puzzle" => GameMode::Puzzle { challenge },
+            "pc" => GameMode::PerfectClear,
+            "drill" => GameMode::Drill,
+            "versus" => GameMode::Versus { rounds },
+            "demo" => GameMode::Demo,
+            _ => return Err(format!("unknown mode '{mode}'")),
+        };
+
+        if options.network.is_some() && (options.ai.is_some() || options.bot.is_some()) {
+            return Err(String::from("the computer cannot play networked matches"));
+        }
+        if options.network.is_some() && !matches!(options.mode, GameMode::Versus { .. }) {
+            return Err(String::from(
+                "--host and --join are only available in versus mode",
+            ));
+        }
+
+        if options.narrate && matches!(options.mode, GameMode::Versus { .. }) {
+            return Err(String::from("--narrate is not available in versus mode"));
+        }
+
+        Ok(options)
+    }
+
+    fn value_of(option: &str, value: Option<String>) -> Result<String, String> {
+        value.ok_or_else(|| format!("missing value for option '{option}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn theme_needs_colored_blocks() {
+        assert!(parse("--style emoji --theme nes").is_err());
+        assert!(parse("--style ansi --theme nes").is_ok());
+        assert!(parse("--style emoji").is_ok());
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+/// Width of the menu items, selection mark included, so that they line up
+/// once centered in the well.
+const ITEM_WIDTH: usize = 18;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Item {
+    Resume,
+    Restart,
+    Options,
+    Quit,
+    Stats,
+    Hints,
+    Music,
+    Back,
+}
+
+const MAIN_ITEMS: [Item; 4] = [Item::Resume, Item::Restart, Item::Options, Item::Quit];
+const OPTION_ITEMS: [Item; 4] = [Item::Stats, Item::Hints, Item::Music, Item::Back];
+
+impl Item {
+    fn get_label(self) -> &'static str {
+        match self {
+            Self::Resume => "RESUME",
+            Self::Restart => "RESTART",
+            Self::Options => "OPTIONS",
+            Self::Quit => "QUIT",
+            Self::Stats => "STATISTICS",
+            Self::Hints => "HINTS",
+            Self::Music => "MUSIC",
+            Self::Back => "BACK",
+        }
+    }
+}
+
+/// The menu shown over the well while the game is paused, which also keeps
+/// when the pause started, for the game timers to skip it.
+pub struct PauseMenu {
+    options: bool,
+    paused_at: Instant,
+    selected: usize,
+}
+
+impl PauseMenu {
+    pub fn new() -> Self {
+        Self {
+            options: false,
+            paused_at: Instant::now(),
+            selected: 0,
+        }
+    }
+
+    pub fn up(&mut self) {
+        let count = self.get_items().len();
+        self.selected = (self.selected + count - 1) % count;
+    }
+
+    pub fn down(&mut self) {
+        self.selected = (self.selected + 1) % self.get_items().len();
+    }
+
+    /// Picks the selected item. The options page opens and closes right away,
+    /// the other items are for the game to carry out.
+    pub fn pick(&mut self) -> Option<Item> {
+        match self.get_items()[self.selected] {
+            Item::Options => {
+                self.options = true;
+                self.selected = 0;
+                None
+            }
+            Item::Back => {
+                self.back();
+                None
+            }
+            item => Some(item),
+        }
+    }
+
+    /// Goes back from the options page to the main one. Tells whether there
+    /// was a page to leave.
+    pub fn back(&mut self) -> bool {
+        if !self.options {
+            return false;
+        }
+
+        self.options = false;
+        self.selected = MAIN_ITEMS
+            .iter()
+            .position(|item| *item == Item::Options)
+            .unwrap_or_default();
+        true
+    }
+
+    /// Returns how long the game has been paused.
+    pub fn get_duration(&self) -> Duration {
+        self.paused_at.elapsed()
+    }
+
+    /// Returns the lines of the menu, given whether each setting is on.
+    pub fn get_lines(&self, is_on: impl Fn(Item) -> Option<bool>) -> Vec<String> {
+        let mut lines = vec![String::from("PAUSED"), String::new()];
+        for (i, item) in self.get_items().iter().enumerate() {
+            let mark = if i == self.selected { '>' } else { ' ' };
+            let label = match is_on(*item) {
+                Some(true) => format!("{}: ON", item.get_label()),
+                Some(false) => format!("{}: OFF", item.get_label()),
+                None => item.get_label().to_string(),
+            };
+            lines.push(format!("{mark} {label:<width$}", width = ITEM_WIDTH - 2));
+        }
+
+        lines
+    }
+
+    // Private functions
+
+    fn get_items(&self) -> &'static [Item] {
+        if self.options {
+            &OPTION_ITEMS
+        } else {
+            &MAIN_ITEMS
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browse_the_menu() {
+        let mut menu = PauseMenu::new();
+        menu.up();
+        assert_eq!(Some(Item::Quit), menu.pick());
+        menu.down();
+        assert_eq!(Some(Item::Resume), menu.pick());
+
+        menu.down();
+        menu.down();
+        assert_eq!(None, menu.pick());
+        assert_eq!(
+            "> STATISTICS: OFF ",
+            menu.get_lines(|item| (item == Item::Stats).then_some(false))[2]
+        );
+        assert_eq!(Some(Item::Stats), menu.pick());
+
+        assert!(menu.back());
+        assert!(!menu.back());
+        assert_eq!("> OPTIONS         ", menu.get_lines(|_| None)[4]);
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Serialize, Serializer};
+
 use crate::utils::{self, Direction, Position, Rotation};
 
 const SHAPE_SIZE: usize = 4;
@@ -8,6 +10,7 @@ pub enum Cell {
     Black,
     Blue,
     Brown,
+    Gray,
     Green,
     Orange,
     Purple,
@@ -15,23 +18,51 @@ pub enum Cell {
     Yellow,
 }
 
-impl std::fmt::Display for Cell {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let c = match self {
-            Cell::Black => '⬛',
-            Cell::Blue => '🟦',
-            Cell::Brown => '🟫',
-            Cell::Green => '🟩',
-            Cell::Orange => '🟧',
-            Cell::Purple => '🟪',
-            Cell::Red => '🟥',
-            Cell::Yellow => '🟨',
-        };
-        write!(f, "{c}")
+impl Cell {
+    /// Returns the character representing this cell in board files. Each
+    /// color is named after the tetromino having it, garbage is an 'X'.
+    pub fn to_char(self) -> char {
+        match self {
+            Cell::Black => '.',
+            Cell::Blue => 'J',
+            Cell::Brown => 'I',
+            Cell::Gray => 'X',
+            Cell::Green => 'S',
+            Cell::Orange => 'L',
+            Cell::Purple => 'T',
+            Cell::Red => 'Z',
+            Cell::Yellow => 'O',
+        }
     }
 }
 
-#[derive(Clone)]
+/// Cells are written as their character in board files.
+impl Serialize for Cell {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_char(self.to_char())
+    }
+}
+
+impl TryFrom<char> for Cell {
+    type Error = String;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            '.' => Ok(Cell::Black),
+            'J' => Ok(Cell::Blue),
+            'I' => Ok(Cell::Brown),
+            'X' => Ok(Cell::Gray),
+            'S' => Ok(Cell::Green),
+            'L' => Ok(Cell::Orange),
+            'T' => Ok(Cell::Purple),
+            'Z' => Ok(Cell::Red),
+            'O' => Ok(Cell::Yellow),
+            _ => Err(format!("invalid cell '{value}'")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum Tetromino {
     I,
     J,
@@ -57,7 +88,36 @@ impl From<usize> for Tetromino {
     }
 }
 
+impl TryFrom<char> for Tetromino {
+    type Error = String;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            'I' => Ok(Self::I),
+            'J' => Ok(Self::J),
+            'L' => Ok(Self::L),
+            'O' => Ok(Self::O),
+            'S' => Ok(Self::S),
+            'T' => Ok(Self::T),
+            'Z' => Ok(Self::Z),
+            _ => Err(format!("invalid tetromino '{value}'")),
+        }
+    }
+}
+
 impl Tetromino {
+    pub fn to_char(&self) -> char {
+        match self {
+            Self::I => 'I',
+            Self::J => 'J',
+            Self::L => 'L',
+            Self::O => 'O',
+            Self::S => 'S',
+            Self::T => 'T',
+            Self::Z => 'Z',
+        }
+    }
+
     pub fn get_spawn_position(&self) -> Position {
         match self {
             Self::I | Self::L => Position::new(0, 3),
@@ -145,21 +205,6 @@ pub struct Piece {
     tetromino: Tetromino,
 }
 
-impl std::fmt::Display for Piece {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let shape = self.tetromino.get_shape();
-        for row in shape.iter().take(SHAPE_SIZE) {
-            let mut line = String::new();
-            for cell in row.iter().take(SHAPE_SIZE) {
-                line = format!("{line}{}", cell);
-            }
-            let _ = writeln!(f, "{line}");
-        }
-
-        Ok(())
-    }
-}
-
 impl Piece {
     pub fn new(tetromino: Tetromino, position: Position) -> Self {
         Self {
@@ -198,10 +243,32 @@ impl Piece {
         self.rotate_shape();
     }
 
+    pub fn get_tetromino(&self) -> &Tetromino {
+        &self.tetromino
+    }
+
     pub fn get_position(&self) -> &Position {
         &self.position
     }
 
+    pub fn get_orientation(&self) -> &Direction {
+        &self.orientation
+    }
+
+    /// Returns the board coordinates of the cells taken by the piece.
+    pub fn get_cells(&self) -> Vec<(isize, isize)> {
+        let mut cells = Vec::new();
+        for row in 0..SHAPE_SIZE {
+            for col in 0..SHAPE_SIZE {
+                if self.has_cell_at(row, col) {
+                    cells.push(utils::to_board_coord(&self.position, row, col));
+                }
+            }
+        }
+
+        cells
+    }
+
     pub fn get_size(&self) -> usize {
         SHAPE_SIZE
     }
@@ -0,0 +1,123 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    pieces::{Piece, Tetromino},
+    utils::{self, Score},
+};
+
+const PIECE_DROP_MICROSECONDS: f64 = 1_000_000.0;
+
+pub const LEVEL_INC_LINES: u32 = 5;
+
+/// The part of the game state belonging to a single player: the piece queue,
+/// the score and the level.
+#[derive(Clone)]
+pub struct Player {
+    /// Pieces dealt every time the bag runs out, instead of a shuffled bag
+    /// holding one of each tetromino.
+    pub fixed_bag: Option<Vec<Tetromino>>,
+    pub level: u32,
+    pub next_piece: Option<Piece>,
+    pub random_bag: Vec<Tetromino>,
+    /// Whether the bag gets refilled once it runs out.
+    pub refill: bool,
+    pub score: Score,
+    rng: StdRng,
+}
+
+impl Player {
+    pub fn new() -> Self {
+        Self {
+            fixed_bag: None,
+            level: 0,
+            next_piece: None,
+            random_bag: Vec::new(),
+            refill: true,
+            score: Score::default(),
+            rng: StdRng::from_os_rng(),
+        }
+    }
+
+    /// Same as `Player::new`, with the bags shuffled the same way every time
+    /// for the same seed.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            ..Self::new()
+        }
+    }
+
+    /// Returns the piece to play next, or none when the bag ran out of pieces.
+    pub fn get_piece(&mut self) -> Option<Piece> {
+        let piece = if let Some(p) = self.next_piece.take() {
+            Some(p)
+        } else {
+            self.take_from_random_bag()
+        };
+
+        self.next_piece = self.take_from_random_bag();
+
+        piece
+    }
+
+    /// Empties the queue, the next pieces come from a fresh bag.
+    pub fn clear_queue(&mut self) {
+        self.random_bag = Vec::new();
+        self.next_piece = None;
+    }
+
+    pub fn increment_score(&mut self, score: Score) {
+        self.score.increment(score);
+        self.level = self.score.lines_destroyed as u32 / LEVEL_INC_LINES;
+    }
+
+    /// Returns the gravity of the current level, in rows per frame.
+    pub fn get_gravity(&self) -> f64 {
+        utils::FRAME_DURATION.as_micros() as f64
+            / (PIECE_DROP_MICROSECONDS * self.get_game_speed_micros())
+    }
+
+    pub fn get_game_speed_micros(&self) -> f64 {
+        let float_level = self.level as f64;
+
+        (0.8 - (float_level * 0.007)).powf(float_level)
+    }
+}
+
+// Private functions
+impl Player {
+    fn take_from_random_bag(&mut self) -> Option<Piece> {
+        if self.random_bag.is_empty() && self.refill {
+            self.refill_random_bag();
+        }
+
+        let tetromino = self.random_bag.pop()?;
+        let position = tetromino.get_spawn_position();
+
+        Some(Piece::new(tetromino, position))
+    }
+
+    fn refill_random_bag(&mut self) {
+        if let Some(bag) = self.fixed_bag.as_ref() {
+            self.random_bag = bag.iter().rev().cloned().collect();
+            return;
+        }
+
+        let mut bag = vec![
+            Tetromino::I,
+            Tetromino::J,
+            Tetromino::L,
+            Tetromino::O,
+            Tetromino::S,
+            Tetromino::T,
+            Tetromino::Z,
+        ];
+
+        self.random_bag = Vec::new();
+        while !bag.is_empty() {
+            let index = self.rng.random_range(0..bag.len());
+            let tetromino = bag.remove(index);
+            self.random_bag.push(tetromino);
+        }
+    }
+}
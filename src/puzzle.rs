@@ -0,0 +1,214 @@
+use std::{fmt::Display, path::PathBuf, str::FromStr};
+
+use crate::{board::Board, context::ASSET_PATH, pieces::Tetromino, utils::LockResult};
+
+const CHALLENGES_DIR: &str = "challenges";
+const PROGRESS_FILE: &str = ".textris-puzzle";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    ClearLines(usize),
+    PerfectClear,
+    TSpinDouble,
+}
+
+impl Goal {
+    pub fn is_reached(&self, lines_destroyed: usize, lock: &LockResult) -> bool {
+        match self {
+            Self::ClearLines(lines) => lines_destroyed >= *lines,
+            Self::PerfectClear => lock.perfect_clear,
+            Self::TSpinDouble => lock.tspin && lock.lines == 2,
+        }
+    }
+}
+
+impl FromStr for Goal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<&str>>();
+
+        match words.as_slice() {
+            ["lines", lines] => match lines.parse::<usize>() {
+                Ok(lines) if lines > 0 => Ok(Self::ClearLines(lines)),
+                _ => Err(format!("invalid number of lines '{lines}'")),
+            },
+            ["perfect-clear"] => Ok(Self::PerfectClear),
+            ["tspin-double"] => Ok(Self::TSpinDouble),
+            _ => Err(format!("invalid goal '{s}'")),
+        }
+    }
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ClearLines(1) => write!(f, "CLEAR 1 LINE"),
+            Self::ClearLines(lines) => write!(f, "CLEAR {lines} LINES"),
+            Self::PerfectClear => write!(f, "PERFECT CLEAR"),
+            Self::TSpinDouble => write!(f, "T-SPIN DOUBLE"),
+        }
+    }
+}
+
+/// A predefined board, together with the pieces to play on it and the goal to
+/// reach with them. Challenges are read from text files looking like this:
+///
+/// ```text
+/// title: Tetris ready
+/// goal: lines 4
+/// pieces: I
+/// board:
+/// XXXXXXXXX.
+/// XXXXXXXXX.
+/// ```
+///
+/// The goal is one of `lines <N>`, `perfect-clear` or `tspin-double`, while the
+/// board rows follow the format of `Board::from_text`.
+#[derive(Clone)]
+pub struct Challenge {
+    pub board: Board,
+    pub goal: Goal,
+    pub number: usize,
+    pub pieces: Vec<Tetromino>,
+    pub title: String,
+}
+
+impl Challenge {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut title = String::new();
+        let mut goal = None;
+        let mut pieces = Vec::new();
+
+        let mut lines = text.lines();
+        for line in lines.by_ref() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once(':') {
+                Some(("board", _)) => break,
+                Some(("title", value)) => title = value.trim().to_string(),
+                Some(("goal", value)) => goal = Some(value.trim().parse()?),
+                Some(("pieces", value)) => {
+                    pieces = value
+                        .trim()
+                        .chars()
+                        .map(Tetromino::try_from)
+                        .collect::<Result<Vec<Tetromino>, String>>()?;
+                }
+                _ => return Err(format!("invalid line '{line}'")),
+            }
+        }
+
+        let board = Board::from_text(&lines.collect::<Vec<&str>>().join("\n"))?;
+        let goal = goal.ok_or("the goal is missing")?;
+        if pieces.is_empty() {
+            return Err(String::from("the pieces are missing"));
+        }
+
+        Ok(Self {
+            board,
+            goal,
+            number: 0,
+            pieces,
+            title,
+        })
+    }
+
+    pub fn load(number: usize) -> Result<Self, String> {
+        let path = format!("{ASSET_PATH}/{CHALLENGES_DIR}/{number:02}.txt");
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read challenge {number} from '{path}': {e}"))?;
+
+        let mut challenge =
+            Self::parse(&text).map_err(|e| format!("invalid challenge {number}: {e}"))?;
+        challenge.number = number;
+
+        Ok(challenge)
+    }
+}
+
+pub fn count_challenges() -> usize {
+    (1..)
+        .take_while(|number| {
+            std::fs::exists(format!("{ASSET_PATH}/{CHALLENGES_DIR}/{number:02}.txt"))
+                .unwrap_or(false)
+        })
+        .count()
+}
+
+/// Returns the highest challenge unlocked so far, the first one is always open.
+pub fn load_progress() -> usize {
+    progress_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|text| text.trim().parse::<usize>().ok())
+        .unwrap_or(1)
+        .max(1)
+}
+
+pub fn save_progress(unlocked: usize) {
+    if unlocked > load_progress()
+        && let Some(path) = progress_path()
+    {
+        let _ = std::fs::write(path, format!("{unlocked}\n"));
+    }
+}
+
+fn progress_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(PROGRESS_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_challenge() {
+        let text = "title: Test\ngoal: lines 2\npieces: OI\nboard:\nXXXX..XXXX\nXXXX..XXXX\n";
+        let challenge = Challenge::parse(text).unwrap();
+
+        assert_eq!("Test", challenge.title);
+        assert_eq!(Goal::ClearLines(2), challenge.goal);
+        assert_eq!(vec![Tetromino::O, Tetromino::I], challenge.pieces);
+        assert_eq!(2, challenge.board.count_garbage_rows());
+    }
+
+    #[test]
+    fn parse_invalid_challenges() {
+        assert!(Challenge::parse("pieces: O\nboard:\n").is_err());
+        assert!(Challenge::parse("goal: lines 2\nboard:\n").is_err());
+        assert!(Challenge::parse("goal: lines 0\npieces: O\nboard:\n").is_err());
+        assert!(Challenge::parse("goal: lines 2\npieces: OQ\nboard:\n").is_err());
+        assert!(Challenge::parse("goal: win\npieces: O\nboard:\n").is_err());
+    }
+
+    #[test]
+    fn goals() {
+        let tsd = LockResult {
+            lines: 2,
+            tspin: true,
+            perfect_clear: false,
+            ..LockResult::default()
+        };
+
+        assert!(Goal::ClearLines(2).is_reached(3, &LockResult::default()));
+        assert!(!Goal::ClearLines(4).is_reached(3, &tsd));
+        assert!(Goal::TSpinDouble.is_reached(2, &tsd));
+        assert!(!Goal::PerfectClear.is_reached(2, &tsd));
+    }
+
+    #[test]
+    fn bundled_challenges_load() {
+        let count = count_challenges();
+        assert!(count > 0);
+
+        for number in 1..=count {
+            let challenge = Challenge::load(number).unwrap();
+            assert_eq!(number, challenge.number);
+        }
+    }
+}
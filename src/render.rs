@@ -0,0 +1,900 @@
+use std::{
+    io::{Stdout, Write},
+    str::FromStr,
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+    QueueableCommand,
+};
+
+use crate::{
+    board::{Board, GameEvent, BOARD_HEIGHT, BOARD_WIDTH},
+    pieces::{Cell, Piece},
+    theme::{Rgb, Theme},
+    utils::Direction,
+};
+
+/// Terminal column of the side panel, right of the board.
+pub const PANEL_COLUMN: u16 = 29;
+/// Terminal column of the next piece preview, within the side panel.
+pub const PREVIEW_COLUMN: u16 = PANEL_COLUMN + 15;
+/// Terminal column of the statistics panel, right of the side panel and
+/// still within 80 columns.
+pub const STATS_COLUMN: u16 = PANEL_COLUMN + 27;
+
+/// A square of the playfield, drawn two terminal columns wide, except for
+/// the walls and corners of some styles.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tile {
+    Cell(Cell),
+    /// An empty cell outlined as the placement to aim for.
+    Target,
+    /// A cell lit up by an animation.
+    Flash,
+    /// An empty cell a piece just went through on a hard drop.
+    Trail,
+    Wall,
+    Floor,
+    /// Where a wall meets the floor.
+    Corner(Side),
+    /// The arrow of a key, in the side panel.
+    Arrow(Direction),
+    Blank,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Part of a line on the screen, either plain text or playfield tiles.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Span {
+    Text(String),
+    Tiles(Vec<Tile>),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Line(pub Vec<Span>);
+
+impl From<String> for Line {
+    fn from(text: String) -> Self {
+        Self(vec![Span::Text(text)])
+    }
+}
+
+impl From<&str> for Line {
+    fn from(text: &str) -> Self {
+        Self::from(text.to_string())
+    }
+}
+
+impl Line {
+    pub fn push_tiles(&mut self, tiles: Vec<Tile>) {
+        self.0.push(Span::Tiles(tiles));
+    }
+}
+
+/// Everything there is to see of the game at some point, as columns of lines
+/// each starting at a terminal column. Renderers only read it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct View {
+    pub columns: Vec<(u16, Vec<Line>)>,
+}
+
+impl View {
+    pub fn add_column(&mut self, col: u16, lines: Vec<Line>) {
+        self.columns.push((col, lines));
+    }
+
+    pub fn get_rows(&self) -> usize {
+        self.columns
+            .iter()
+            .map(|(_, lines)| lines.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Returns the lines drawing the board, active piece and target included,
+/// between the walls and above the floor.
+pub fn board_lines(board: &Board) -> Vec<Line> {
+    well_lines(board_tiles(board))
+}
+
+/// Returns the tiles of the board cells, active piece and target included.
+pub fn board_tiles(board: &Board) -> Vec<Vec<Tile>> {
+    (0..BOARD_HEIGHT)
+        .map(|row| {
+            (0..BOARD_WIDTH)
+                .map(|col| match board.get_cell(row, col) {
+                    Cell::Black if board.is_target(row, col) => Tile::Target,
+                    cell => Tile::Cell(cell),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the lines drawing rows of tiles between the walls and above the
+/// floor.
+pub fn well_lines(tiles: Vec<Vec<Tile>>) -> Vec<Line> {
+    let mut lines = tiles
+        .into_iter()
+        .map(|row| {
+            let mut tiles = vec![Tile::Wall];
+            tiles.extend(row);
+            tiles.push(Tile::Wall);
+
+            Line(vec![Span::Tiles(tiles)])
+        })
+        .collect::<Vec<Line>>();
+    let mut floor = vec![Tile::Corner(Side::Left)];
+    floor.extend([Tile::Floor; BOARD_WIDTH]);
+    floor.push(Tile::Corner(Side::Right));
+    lines.push(Line(vec![Span::Tiles(floor)]));
+
+    lines
+}
+
+/// Returns the lines drawing an empty well with some text in its middle, such
+/// as the pause menu.
+pub fn message_lines(text: &[String]) -> Vec<Line> {
+    let mut lines = well_lines(vec![
+        vec![Tile::Cell(Cell::Black); BOARD_WIDTH];
+        BOARD_HEIGHT
+    ]);
+    let top = BOARD_HEIGHT.saturating_sub(text.len()) / 2;
+    for (line, text) in lines.iter_mut().skip(top).zip(text) {
+        // every cell of the well is two columns wide
+        let width = BOARD_WIDTH * 2;
+        *line = Line(vec![
+            Span::Tiles(vec![Tile::Wall]),
+            Span::Text(format!("{text:^width$}")),
+            Span::Tiles(vec![Tile::Wall]),
+        ]);
+    }
+
+    lines
+}
+
+/// Returns the lines drawing a piece on its own, such as the next piece.
+pub fn piece_lines(piece: &Piece) -> Vec<Line> {
+    (0..piece.get_size())
+        .map(|row| {
+            let tiles = (0..piece.get_size())
+                .map(|col| Tile::Cell(*piece.get_cell_at(row, col)))
+                .collect();
+            Line(vec![Span::Tiles(tiles)])
+        })
+        .collect()
+}
+
+/// Draws the views of the game somewhere.
+pub trait Renderer {
+    fn render(&mut self, view: &View) -> std::io::Result<()>;
+
+    /// Lays the views out again for a terminal of the given size.
+    fn resize(&mut self, _width: u16, _height: u16) {}
+
+    /// Tells whether the last view didn't fit, and couldn't be shown.
+    fn is_too_small(&self) -> bool {
+        false
+    }
+
+    /// Follows what happens on the board, for the renderers telling the game
+    /// rather than drawing it.
+    fn observe(&mut self, _event: &GameEvent) {}
+
+    /// Tells the player something the views don't show.
+    fn announce(&mut self, _text: &str) {}
+
+    /// Tells whether the renderer writes lines one after the other, which
+    /// must stay on the main screen for the screen readers to follow them.
+    fn is_linear(&self) -> bool {
+        false
+    }
+}
+
+/// How the tiles look on a terminal.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Style {
+    /// Colored square emoji, the look textris always had.
+    #[default]
+    Emoji,
+    /// Plain characters only, for terminals without colors nor emoji.
+    Ascii,
+    /// `[]` blocks within box-drawing walls, for fonts without emoji.
+    Box,
+    /// Spaces on a colored background, from the 256 colors palette.
+    Ansi,
+    /// Spaces on a colored background, in 24-bit colors.
+    Truecolor,
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::detect()),
+            "emoji" => Ok(Self::Emoji),
+            "ascii" => Ok(Self::Ascii),
+            "box" => Ok(Self::Box),
+            "ansi" => Ok(Self::Ansi),
+            "truecolor" => Ok(Self::Truecolor),
+            _ => Err(format!(
+                "invalid style '{s}', expected 'auto', 'emoji', 'ascii', 'box', 'ansi' or 'truecolor'"
+            )),
+        }
+    }
+}
+
+impl Style {
+    /// Picks the style the terminal most likely shows right, from the `TERM`
+    /// and `COLORTERM` variables and the locale.
+    pub fn detect() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|value| !value.is_empty());
+        let locale = var("LC_ALL")
+            .or_else(|| var("LC_CTYPE"))
+            .or_else(|| var("LANG"));
+
+        Self::detect_from(
+            var("TERM").as_deref(),
+            var("COLORTERM").as_deref(),
+            locale.as_deref(),
+        )
+    }
+
+    /// Picks a style with colored blocks, unless the terminal cannot show
+    /// them.
+    pub fn detect_colored() -> Self {
+        match Self::detect() {
+            Self::Emoji
+                if matches!(
+                    std::env::var("COLORTERM").as_deref(),
+                    Ok("truecolor" | "24bit")
+                ) =>
+            {
+                Self::Truecolor
+            }
+            Self::Emoji => Self::Ansi,
+            style => style,
+        }
+    }
+
+    // Private functions
+
+    fn detect_from(term: Option<&str>, colorterm: Option<&str>, locale: Option<&str>) -> Self {
+        // the Windows consoles set neither, and keep the emoji as they always did
+        if cfg!(windows) && term.is_none() {
+            return Self::Emoji;
+        }
+
+        let utf8 = locale.is_some_and(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        });
+        let truecolor = matches!(colorterm, Some("truecolor" | "24bit"));
+
+        match term.unwrap_or("dumb") {
+            "dumb" => Self::Ascii,
+            _ if !utf8 => Self::Ascii,
+            // the Linux console has no emoji in its fonts, only a few colors
+            "linux" => Self::Box,
+            // emoji widths disagree between multiplexers and the terminals
+            // they run in, which shifts the whole board
+            term if term.starts_with("screen") || term.starts_with("tmux") => {
+                if truecolor {
+                    Self::Truecolor
+                } else if term.contains("256color") {
+                    Self::Ansi
+                } else {
+                    Self::Box
+                }
+            }
+            _ => Self::Emoji,
+        }
+    }
+
+    /// Returns how many terminal columns the text of a tile takes.
+    fn width(&self, text: &str) -> usize {
+        match self {
+            // the emoji are all two columns wide, some with a variation selector
+            Self::Emoji => 2,
+            _ => text.chars().count(),
+        }
+    }
+
+    /// Returns the text of a tile, and the background color behind it.
+    fn tile(&self, theme: &Theme, tile: Tile) -> (String, Option<Rgb>) {
+        let pattern = |cell| theme.get_look(cell).pattern.as_deref();
+        match self {
+            Self::Emoji => {
+                let text = match tile {
+                    Tile::Cell(Cell::Black) => "⬛",
+                    Tile::Cell(Cell::Blue) => "🟦",
+                    Tile::Cell(Cell::Brown) => "🟫",
+                    Tile::Cell(Cell::Gray) => "⬜",
+                    Tile::Cell(Cell::Green) => "🟩",
+                    Tile::Cell(Cell::Orange) => "🟧",
+                    Tile::Cell(Cell::Purple) => "🟪",
+                    Tile::Cell(Cell::Red) => "🟥",
+                    Tile::Cell(Cell::Yellow) => "🟨",
+                    Tile::Target => "🔲",
+                    Tile::Flash => "⬜",
+                    Tile::Trail => "🔳",
+                    Tile::Wall | Tile::Floor | Tile::Corner(_) => "🧱",
+                    Tile::Arrow(direction) => match direction {
+                        Direction::Up => "⬆️",
+                        Direction::Down => "⬇️",
+                        Direction::Left => "⬅️",
+                        Direction::Right => "➡️",
+                    },
+                    Tile::Blank => "  ",
+                };
+                (text.to_string(), None)
+            }
+            Self::Ascii => {
+                let text = match tile {
+                    Tile::Cell(Cell::Black) => " .",
+                    Tile::Cell(cell)
+                        if let Some(pattern) = pattern(cell)
+                            && pattern.is_ascii() =>
+                    {
+                        pattern
+                    }
+                    Tile::Cell(Cell::Gray) => "##",
+                    Tile::Cell(_) => "[]",
+                    Tile::Target => "::",
+                    Tile::Flash => "**",
+                    Tile::Trail => " :",
+                    Tile::Wall => "|",
+                    Tile::Floor => "--",
+                    Tile::Corner(_) => "+",
+                    Tile::Arrow(direction) => ascii_arrow(direction),
+                    Tile::Blank => "  ",
+                };
+                (text.to_string(), None)
+            }
+            Self::Box => {
+                let text = match tile {
+                    Tile::Cell(Cell::Black) => " ·",
+                    Tile::Cell(cell) if let Some(pattern) = pattern(cell) => pattern,
+                    Tile::Cell(Cell::Gray) => "▒▒",
+                    Tile::Cell(_) => "[]",
+                    Tile::Target => "░░",
+                    Tile::Flash => "██",
+                    Tile::Trail => " ┊",
+                    Tile::Wall => "│",
+                    Tile::Floor => "──",
+                    Tile::Corner(Side::Left) => "└",
+                    Tile::Corner(Side::Right) => "┘",
+                    Tile::Arrow(direction) => unicode_arrow(direction),
+                    Tile::Blank => "  ",
+                };
+                (text.to_string(), None)
+            }
+            Self::Ansi | Self::Truecolor => {
+                let (text, color) = match tile {
+                    Tile::Cell(cell) => {
+                        let look = theme.get_look(cell);
+                        (look.pattern.as_deref().unwrap_or("  "), Some(look.color))
+                    }
+                    Tile::Target => ("[]", Some(theme.get_look(Cell::Black).color)),
+                    Tile::Flash => ("  ", Some(Rgb(255, 255, 255))),
+                    Tile::Trail => ("  ", Some(theme.get_wall().color)),
+                    Tile::Wall | Tile::Floor | Tile::Corner(_) => {
+                        let wall = theme.get_wall();
+                        (wall.pattern.as_deref().unwrap_or("  "), Some(wall.color))
+                    }
+                    Tile::Arrow(direction) => (unicode_arrow(direction), None),
+                    Tile::Blank => ("  ", None),
+                };
+                (text.to_string(), color)
+            }
+        }
+    }
+
+    /// Returns a color as the terminal takes it in this style.
+    fn color(&self, rgb: Rgb) -> Color {
+        match self {
+            Self::Truecolor => Color::Rgb {
+                r: rgb.0,
+                g: rgb.1,
+                b: rgb.2,
+            },
+            _ => Color::AnsiValue(rgb.to_ansi()),
+        }
+    }
+}
+
+fn ascii_arrow(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "^",
+        Direction::Down => "v",
+        Direction::Left => "<-",
+        Direction::Right => "->",
+    }
+}
+
+fn unicode_arrow(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "↑",
+        Direction::Down => "↓",
+        Direction::Left => "←",
+        Direction::Right => "→",
+    }
+}
+
+/// What occupies one terminal column of the screen.
+#[derive(Clone, Debug, PartialEq)]
+enum Slot {
+    Empty,
+    Char(char),
+    /// A tile starting at this column, with its background color.
+    Tile(String, Option<Rgb>),
+    /// The columns of a tile after the first one.
+    Covered,
+}
+
+/// The screen as a grid of slots, one per terminal column.
+type Frame = Vec<Vec<Slot>>;
+
+/// Draws on the terminal, in the given style, centered. The previous frame
+/// is kept, and only the columns which changed since are written again.
+pub struct TerminalRenderer<W: Write = Stdout> {
+    out: W,
+    style: Style,
+    theme: Theme,
+    previous: Frame,
+    /// Size of the terminal, unknown when not drawing on one.
+    size: Option<(u16, u16)>,
+    too_small: bool,
+}
+
+impl TerminalRenderer {
+    pub fn new(style: Style, theme: Theme) -> Self {
+        Self::with_output(std::io::stdout(), style, theme, terminal::size().ok())
+    }
+}
+
+impl<W: Write> TerminalRenderer<W> {
+    /// Draws on the given output, in a terminal of the given size.
+    pub fn with_output(out: W, style: Style, theme: Theme, size: Option<(u16, u16)>) -> Self {
+        Self {
+            out,
+            style,
+            theme,
+            previous: Frame::new(),
+            size,
+            too_small: false,
+        }
+    }
+
+    // Private functions
+
+    /// Moves the frame to the middle of the terminal, or replaces it with a
+    /// message when the terminal is too small for it.
+    fn place_frame(&mut self, mut frame: Frame) -> Frame {
+        let Some((columns, rows)) = self.size.map(|(w, h)| (w as usize, h as usize)) else {
+            return frame;
+        };
+
+        let width = frame.iter().map(Vec::len).max().unwrap_or(0);
+        let height = frame.len();
+        self.too_small = width > columns || height > rows;
+        if self.too_small {
+            let message = format!("terminal too small (need {width}x{height})");
+            let mut view = View::default();
+            view.add_column(0, vec![Line::from(message.as_str())]);
+            frame = self.build_frame(&view);
+        }
+
+        let width = frame.iter().map(Vec::len).max().unwrap_or(0);
+        let left = columns.saturating_sub(width) / 2;
+        let top = rows.saturating_sub(frame.len()) / 2;
+        for slots in &mut frame {
+            slots.splice(0..0, std::iter::repeat_n(Slot::Empty, left));
+        }
+        frame.splice(0..0, std::iter::repeat_n(Vec::new(), top));
+
+        frame
+    }
+
+    /// Lays the view out on a grid of slots.
+    fn build_frame(&self, view: &View) -> Frame {
+        let mut frame = vec![Vec::new(); view.get_rows()];
+
+        for (col, lines) in &view.columns {
+            for (slots, line) in frame.iter_mut().zip(lines) {
+                let mut x = *col as usize;
+                let mut put = |slot, width: usize| {
+                    if slots.len() < x + width {
+                        slots.resize(x + width, Slot::Empty);
+                    }
+                    slots[x] = slot;
+                    slots[x + 1..x + width].fill(Slot::Covered);
+                    x += width;
+                };
+
+                for span in &line.0 {
+                    match span {
+                        Span::Text(text) => text.chars().for_each(|c| put(Slot::Char(c), 1)),
+                        Span::Tiles(tiles) => tiles.iter().for_each(|tile| {
+                            let (text, background) = self.style.tile(&self.theme, *tile);
+                            let width = self.style.width(&text);
+                            put(Slot::Tile(text, background), width);
+                        }),
+                    }
+                }
+            }
+        }
+
+        frame
+    }
+
+    /// Writes the slots of a row which differ from the previous frame.
+    fn write_row(&mut self, row: usize, slots: &[Slot]) -> std::io::Result<()> {
+        let previous = self
+            .previous
+            .get(row)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut cursor = None;
+
+        for x in 0..slots.len().max(previous.len()) {
+            let slot = slots.get(x).unwrap_or(&Slot::Empty);
+            if slot == previous.get(x).unwrap_or(&Slot::Empty) || *slot == Slot::Covered {
+                continue;
+            }
+            if cursor != Some(x) {
+                self.out.queue(MoveTo(x as u16, row as u16))?;
+            }
+
+            match slot {
+                Slot::Empty => {
+                    self.out.queue(Print(' '))?;
+                    cursor = Some(x + 1);
+                }
+                Slot::Char(c) => {
+                    self.out.queue(Print(*c))?;
+                    cursor = Some(x + 1);
+                }
+                Slot::Tile(text, background) => {
+                    match *background {
+                        Some(rgb) => {
+                            let foreground = if rgb.is_light() {
+                                Color::Black
+                            } else {
+                                Color::White
+                            };
+                            self.out
+                                .queue(SetBackgroundColor(self.style.color(rgb)))?
+                                .queue(SetForegroundColor(foreground))?
+                                .queue(Print(text))?
+                                .queue(ResetColor)?
+                        }
+                        None => self.out.queue(Print(text))?,
+                    };
+                    cursor = Some(x + self.style.width(text));
+                }
+                Slot::Covered => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> Renderer for TerminalRenderer<W> {
+    fn render(&mut self, view: &View) -> std::io::Result<()> {
+        let frame = self.build_frame(view);
+        let frame = self.place_frame(frame);
+
+        for row in 0..frame.len().max(self.previous.len()) {
+            self.write_row(row, frame.get(row).map(Vec::as_slice).unwrap_or_default())?;
+        }
+        self.previous = frame;
+
+        self.out.flush()
+    }
+
+    fn resize(&mut self, width: u16, height: u16) {
+        // some terminals keep parts of the old screen around, draw anew
+        let _ = self.out.queue(Clear(ClearType::All));
+        self.previous.clear();
+        self.size = Some((width, height));
+    }
+
+    fn is_too_small(&self) -> bool {
+        self.too_small
+    }
+}
+
+/// Keeps the last view as plain text, every tile written as two characters:
+/// the board file character of its cell twice, `##` for the walls and floor,
+/// `<>` for the target, and as the ascii style for the animations. Meant for
+/// comparing with golden files in tests.
+#[cfg(test)]
+#[derive(Default)]
+pub struct SnapshotRenderer {
+    pub snapshot: String,
+}
+
+#[cfg(test)]
+impl Renderer for SnapshotRenderer {
+    fn render(&mut self, view: &View) -> std::io::Result<()> {
+        let mut rows = vec![String::new(); view.get_rows()];
+
+        for (col, lines) in &view.columns {
+            for (row, line) in rows.iter_mut().zip(lines) {
+                let width = row.chars().count();
+                row.push_str(&" ".repeat((*col as usize).saturating_sub(width)));
+
+                for span in &line.0 {
+                    match span {
+                        Span::Text(text) => row.push_str(text),
+                        Span::Tiles(tiles) => tiles.iter().for_each(|tile| match tile {
+                            Tile::Cell(cell) => row.extend([cell.to_char(); 2]),
+                            Tile::Target => row.push_str("<>"),
+                            Tile::Flash => row.push_str("**"),
+                            Tile::Trail => row.push_str(" :"),
+                            Tile::Wall | Tile::Floor | Tile::Corner(_) => row.push_str("##"),
+                            Tile::Arrow(direction) => row.push_str(ascii_arrow(*direction)),
+                            Tile::Blank => row.push_str("  "),
+                        }),
+                    }
+                }
+            }
+        }
+
+        self.snapshot = rows
+            .iter()
+            .map(|row| format!("{}\n", row.trim_end()))
+            .collect();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    use crate::{
+        pieces::Tetromino,
+        utils::{Input, FRAME_DURATION},
+    };
+
+    /// Compares the snapshot with its golden file, or writes the golden file
+    /// when `UPDATE_GOLDEN` is set.
+    fn assert_golden(name: &str, snapshot: &str) {
+        let path = PathBuf::from("tests/golden").join(format!("{name}.txt"));
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, snapshot).unwrap();
+        }
+
+        let golden = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(golden, snapshot, "snapshot differs from {}", path.display());
+    }
+
+    fn sample_board() -> Board {
+        let mut board = Board::from_text("XX..XXXXXX\nXXX.XXXXXX\nIIIIOOTTTZ").unwrap();
+        board.add_piece(Piece::new(Tetromino::T, Tetromino::T.get_spawn_position()));
+        board.set_target(vec![(17, 2), (17, 3), (18, 3), (16, 3)]);
+
+        board
+    }
+
+    #[test]
+    fn board_snapshot() {
+        let board = sample_board();
+        let mut view = View::default();
+        view.add_column(0, board_lines(&board));
+        view.add_column(PANEL_COLUMN, vec![Line::from("NEXT PIECE:")]);
+        view.add_column(
+            PREVIEW_COLUMN,
+            piece_lines(&Piece::new(Tetromino::L, Tetromino::L.get_spawn_position())),
+        );
+
+        let mut renderer = SnapshotRenderer::default();
+        renderer.render(&view).unwrap();
+
+        assert_golden("board", &renderer.snapshot);
+    }
+
+    #[test]
+    fn styles_keep_tiles_aligned() {
+        let theme = Theme::default();
+        for style in [Style::Emoji, Style::Box, Style::Ansi, Style::Truecolor] {
+            for tile in [
+                Tile::Cell(Cell::Black),
+                Tile::Cell(Cell::Red),
+                Tile::Target,
+                Tile::Floor,
+            ] {
+                let (text, _) = style.tile(&theme, tile);
+                assert!(text.chars().count() <= 2, "{style:?} {tile:?}");
+            }
+        }
+        assert_eq!("[]", Style::Ascii.tile(&theme, Tile::Cell(Cell::Purple)).0);
+        assert!(Style::Ascii
+            .tile(&theme, Tile::Arrow(Direction::Left))
+            .0
+            .is_ascii());
+        assert!("braille".parse::<Style>().is_err());
+    }
+
+    #[test]
+    fn detect_style() {
+        let utf8 = Some("en_US.UTF-8");
+
+        assert_eq!(
+            Style::Emoji,
+            Style::detect_from(Some("xterm-256color"), None, utf8)
+        );
+        assert_eq!(Style::Ascii, Style::detect_from(Some("dumb"), None, utf8));
+        assert_eq!(
+            Style::Ascii,
+            Style::detect_from(Some("xterm"), None, Some("C"))
+        );
+        assert_eq!(
+            Style::Box,
+            Style::detect_from(Some("linux"), None, Some("C.utf8"))
+        );
+        assert_eq!(
+            Style::Ansi,
+            Style::detect_from(Some("tmux-256color"), None, utf8)
+        );
+        assert_eq!(
+            Style::Truecolor,
+            Style::detect_from(Some("screen"), Some("truecolor"), utf8)
+        );
+    }
+
+    fn game_view(board: &Board) -> View {
+        let mut view = View::default();
+        view.add_column(0, board_lines(board));
+        view.add_column(PANEL_COLUMN, vec![Line::from("NEXT PIECE:")]);
+
+        view
+    }
+
+    #[test]
+    fn writes_only_changes() {
+        let mut board = sample_board();
+        let mut renderer =
+            TerminalRenderer::with_output(Vec::new(), Style::Ascii, Theme::default(), None);
+
+        renderer.render(&game_view(&board)).unwrap();
+        assert!(renderer.out.len() > 400);
+
+        renderer.out.clear();
+        renderer.render(&game_view(&board)).unwrap();
+        assert!(renderer.out.is_empty());
+
+        board.apply_input(Input::Right);
+        renderer.render(&game_view(&board)).unwrap();
+        let written = String::from_utf8(renderer.out).unwrap();
+        assert!(written.len() < 100, "{written:?}");
+        assert!(written.contains("[]"));
+    }
+
+    /// Processor time spent by the current thread so far, on Linux.
+    fn thread_cpu_time() -> Option<Duration> {
+        let stat = std::fs::read_to_string("/proc/thread-self/stat").ok()?;
+        // the command may hold spaces, the fields after it start with the state
+        let fields = stat
+            .rsplit_once(')')?
+            .1
+            .split_whitespace()
+            .collect::<Vec<&str>>();
+        let ticks = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+
+        // the kernel counts in hundredths of a second
+        Some(Duration::from_millis(ticks * 10))
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --ignored --nocapture"]
+    fn bench_game_loop() {
+        // two seconds of a game with the piece falling a row every 50 ms, first
+        // waking up every millisecond to draw the whole screen, as the game loop
+        // used to, then sleeping until the next frame to draw the changes only
+        let run = |every_millisecond: bool| {
+            let mut board = sample_board();
+            let mut renderer =
+                TerminalRenderer::with_output(Vec::new(), Style::Emoji, Theme::default(), None);
+            let (mut wakeups, mut bytes) = (0, 0);
+
+            let cpu = thread_cpu_time();
+            let started = Instant::now();
+            let mut next_frame = started;
+            let mut next_drop = started;
+            while started.elapsed() < Duration::from_secs(2) {
+                if every_millisecond {
+                    std::thread::sleep(Duration::from_millis(1));
+                    renderer.previous = Frame::new();
+                } else {
+                    next_frame += FRAME_DURATION;
+                    std::thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+                }
+                wakeups += 1;
+
+                if next_drop.elapsed() >= Duration::from_millis(50) {
+                    next_drop += Duration::from_millis(50);
+                    board.apply_input(Input::SoftDrop);
+                }
+                renderer.render(&game_view(&board)).unwrap();
+                bytes += renderer.out.len();
+                renderer.out.clear();
+            }
+            let cpu = thread_cpu_time().zip(cpu).map(|(now, before)| now - before);
+
+            println!(
+                "{}: {wakeups} wakeups, {cpu:?} of processor time and {bytes} bytes written",
+                if every_millisecond {
+                    "full redraws every millisecond"
+                } else {
+                    "diffs every frame"
+                },
+            );
+        };
+
+        run(true);
+        run(false);
+    }
+
+    #[test]
+    fn centers_in_the_terminal() {
+        let board = sample_board();
+        let mut renderer = TerminalRenderer::with_output(
+            Vec::new(),
+            Style::Ascii,
+            Theme::default(),
+            Some((80, 30)),
+        );
+
+        renderer.render(&game_view(&board)).unwrap();
+        assert!(!renderer.is_too_small());
+        // 40 columns wide with the panel, 21 rows high
+        assert_eq!(
+            Slot::Tile(String::from("|"), None),
+            renderer.previous[4][20]
+        );
+
+        // a view too wide only for a while doesn't keep the game from showing
+        let mut wide = game_view(&board);
+        wide.add_column(75, vec![Line::from("TOO FAR")]);
+        renderer.render(&wide).unwrap();
+        assert!(renderer.is_too_small());
+        renderer.render(&game_view(&board)).unwrap();
+        assert!(!renderer.is_too_small());
+
+        renderer.out.clear();
+        renderer.resize(30, 15);
+        renderer.render(&game_view(&board)).unwrap();
+        assert!(renderer.is_too_small());
+        let written = String::from_utf8(renderer.out).unwrap();
+        assert!(written.contains("terminal too small (need 40x21)"));
+    }
+
+    #[test]
+    fn themes_draw_patterns() {
+        let theme = Theme::load("deuteranopia").unwrap();
+        let (text, background) = Style::Ansi.tile(&theme, Tile::Cell(Cell::Purple));
+        assert_eq!("/\\", text);
+        assert_eq!(Some(Rgb(0xcc, 0x79, 0xa7)), background);
+        assert_eq!("/\\", Style::Ascii.tile(&theme, Tile::Cell(Cell::Purple)).0);
+        // the emoji have their own colors
+        assert_eq!("🟪", Style::Emoji.tile(&theme, Tile::Cell(Cell::Purple)).0);
+    }
+}
@@ -0,0 +1,186 @@
+use std::time::Duration;
+
+use crate::{
+    board::GameEvent,
+    pieces::Tetromino,
+    utils::{Attack, Input},
+};
+
+const TETROMINOES_COUNT: usize = 7;
+
+/// Statistics of the game, gathered from the events of the board and the
+/// inputs of the player.
+#[derive(Default)]
+pub struct Stats {
+    attack: Attack,
+    /// Line clears, by number of lines cleared at once.
+    clears: [usize; 4],
+    inputs: usize,
+    last_input: Option<Input>,
+    lines_sent: usize,
+    max_combo: usize,
+    pieces: usize,
+    tetrominoes: [usize; TETROMINOES_COUNT],
+    tspins: usize,
+}
+
+impl Stats {
+    /// Counts a key pressed by the player, holding the soft drop counts once.
+    pub fn record_input(&mut self, input: Input) {
+        if input != Input::SoftDrop || self.last_input != Some(Input::SoftDrop) {
+            self.inputs += 1;
+        }
+        self.last_input = Some(input);
+    }
+
+    pub fn observe(&mut self, event: &GameEvent) {
+        if let GameEvent::PieceLocked { tetromino, lock } = event {
+            self.last_input = None;
+            self.pieces += 1;
+            self.tetrominoes[Self::index(tetromino)] += 1;
+            if lock.tspin {
+                self.tspins += 1;
+            }
+            if lock.lines > 0 {
+                self.clears[lock.lines.min(4) - 1] += 1;
+            }
+
+            self.lines_sent += self.attack.compute(lock);
+            self.max_combo = self.max_combo.max(self.attack.get_combo());
+        }
+    }
+
+    /// Returns the combo and back to back of the clears so far.
+    pub fn get_attack(&self) -> &Attack {
+        &self.attack
+    }
+
+    pub fn get_pieces(&self) -> usize {
+        self.pieces
+    }
+
+    /// Pieces placed per second.
+    pub fn get_pps(&self, elapsed: Duration) -> f64 {
+        Self::rate(self.pieces, elapsed.as_secs_f64())
+    }
+
+    /// Keys pressed per piece placed.
+    pub fn get_kpp(&self) -> f64 {
+        Self::rate(self.inputs, self.pieces as f64)
+    }
+
+    /// Garbage rows the clears would send, per minute.
+    pub fn get_apm(&self, elapsed: Duration) -> f64 {
+        Self::rate(self.lines_sent, elapsed.as_secs_f64() / 60.0)
+    }
+
+    /// Returns how many times this many lines were cleared at once.
+    pub fn get_clears(&self, lines: usize) -> usize {
+        self.clears[lines.clamp(1, 4) - 1]
+    }
+
+    pub fn get_tspins(&self) -> usize {
+        self.tspins
+    }
+
+    /// Returns the most clears in a row.
+    pub fn get_max_combo(&self) -> usize {
+        self.max_combo
+    }
+
+    /// Returns how many pieces of each kind were placed.
+    pub fn get_tetrominoes(&self) -> Vec<(Tetromino, usize)> {
+        (1..=TETROMINOES_COUNT)
+            .map(Tetromino::from)
+            .zip(self.tetrominoes)
+            .collect()
+    }
+
+    // Private functions
+
+    fn index(tetromino: &Tetromino) -> usize {
+        (1..=TETROMINOES_COUNT)
+            .position(|i| Tetromino::from(i) == *tetromino)
+            .unwrap_or_default()
+    }
+
+    fn rate(count: usize, per: f64) -> f64 {
+        if per > 0.0 {
+            count as f64 / per
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::Board, pieces::Piece, utils::LockResult};
+
+    #[test]
+    fn counts_the_locks() {
+        let mut stats = Stats::default();
+        let mut board = Board::from_text("XXXX..XXXX").unwrap();
+        board.add_piece(Piece::new(Tetromino::O, Tetromino::O.get_spawn_position()));
+        board.apply_input(Input::HardDrop);
+        stats.record_input(Input::HardDrop);
+        for event in board.take_events() {
+            stats.observe(&event);
+        }
+
+        let lock = |lines, tspin| GameEvent::PieceLocked {
+            tetromino: Tetromino::T,
+            lock: LockResult {
+                lines,
+                tspin,
+                ..LockResult::default()
+            },
+        };
+        stats.observe(&lock(2, true));
+        stats.observe(&lock(1, false));
+        stats.observe(&lock(0, false));
+
+        assert_eq!(4, stats.get_pieces());
+        assert_eq!(0.25, stats.get_kpp());
+        assert_eq!(1, stats.get_tspins());
+        assert_eq!(2, stats.get_clears(1));
+        assert_eq!(1, stats.get_clears(2));
+        assert_eq!(0, stats.get_clears(4));
+        assert_eq!(3, stats.get_max_combo());
+        assert_eq!(
+            vec![0, 0, 0, 1, 0, 3, 0],
+            stats
+                .get_tetrominoes()
+                .into_iter()
+                .map(|(_, count)| count)
+                .collect::<Vec<usize>>()
+        );
+
+        // a single, a T-spin double and a single in a row send 0 + 4 + 1 rows
+        assert_eq!(0.5, stats.get_pps(Duration::from_secs(8)));
+        assert_eq!(5.0, stats.get_apm(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn held_soft_drop_counts_once() {
+        let mut stats = Stats::default();
+        for input in [
+            Input::Left,
+            Input::SoftDrop,
+            Input::SoftDrop,
+            Input::SoftDrop,
+        ] {
+            stats.record_input(input);
+        }
+        stats.record_input(Input::HardDrop);
+        stats.observe(&GameEvent::PieceLocked {
+            tetromino: Tetromino::T,
+            lock: LockResult::default(),
+        });
+        // the next piece is soft dropped again, a new press
+        stats.record_input(Input::SoftDrop);
+
+        assert_eq!(4.0, stats.get_kpp());
+    }
+}
@@ -0,0 +1,494 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    board::{Board, BOARD_HEIGHT, BOARD_WIDTH},
+    bot,
+    pieces::{Cell, Piece, Tetromino},
+    utils::{Attack, Input},
+};
+
+/// Rows of the board in the protocol, the ones above ours are always empty.
+const TBP_BOARD_HEIGHT: usize = 40;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const QUIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Messages sent to the bot, one JSON object per line.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FrontendMessage {
+    Rules,
+    Start {
+        hold: Option<char>,
+        queue: Vec<char>,
+        combo: u32,
+        back_to_back: bool,
+        /// Rows from the bottom up, each cell being empty or the letter of the
+        /// piece it belongs to, 'G' for garbage.
+        board: Vec<[Option<char>; BOARD_WIDTH]>,
+    },
+    Suggest,
+    Play {
+        #[serde(rename = "move")]
+        mv: Move,
+    },
+    NewPiece {
+        piece: char,
+    },
+    Stop,
+    Quit,
+}
+
+/// Messages received from the bot, the ones not listed here are ignored.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+    },
+    Ready,
+    Error {
+        reason: String,
+    },
+    Suggestion {
+        moves: Vec<Move>,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Move {
+    pub location: Location,
+    pub spin: String,
+}
+
+/// Where a piece goes, as the position of its center: `x` counts the columns
+/// from the left, `y` the rows from the bottom.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Location {
+    #[serde(rename = "type")]
+    pub piece: char,
+    pub orientation: String,
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Location {
+    /// Returns the board cells covered by the piece, in row major order like
+    /// the cells of a `Placement`, or none when they're not on the board.
+    pub fn get_cells(&self) -> Option<Vec<(usize, usize)>> {
+        let offsets: [(i32, i32); 4] = match Tetromino::try_from(self.piece).ok()? {
+            Tetromino::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            Tetromino::J => [(-1, 1), (-1, 0), (0, 0), (1, 0)],
+            Tetromino::L => [(1, 1), (-1, 0), (0, 0), (1, 0)],
+            Tetromino::O => [(0, 1), (1, 1), (0, 0), (1, 0)],
+            Tetromino::S => [(0, 1), (1, 1), (-1, 0), (0, 0)],
+            Tetromino::T => [(0, 1), (-1, 0), (0, 0), (1, 0)],
+            Tetromino::Z => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+        };
+        let rotate: fn((i32, i32)) -> (i32, i32) = match self.orientation.as_str() {
+            "north" => |(x, y)| (x, y),
+            "east" => |(x, y)| (y, -x),
+            "south" => |(x, y)| (-x, -y),
+            "west" => |(x, y)| (-y, x),
+            _ => return None,
+        };
+
+        let mut cells = offsets
+            .into_iter()
+            .map(|offset| {
+                let (dx, dy) = rotate(offset);
+                let col = usize::try_from(self.x + dx).ok()?;
+                let row = (BOARD_HEIGHT - 1).checked_sub(usize::try_from(self.y + dy).ok()?)?;
+                (col < BOARD_WIDTH).then_some((row, col))
+            })
+            .collect::<Option<Vec<(usize, usize)>>>()?;
+        cells.sort();
+
+        Some(cells)
+    }
+}
+
+/// An external bot, running as a child process speaking the Tetris Bot
+/// Protocol over its standard input and output.
+pub struct Client {
+    child: Child,
+    /// The board and the active piece the bot expects after its last move,
+    /// as long as they match the game the bot only hears of the new pieces.
+    expected: Option<(String, Tetromino)>,
+    messages: Receiver<BotMessage>,
+    name: String,
+    next: Option<Tetromino>,
+    /// Suggestions asked for and not received yet.
+    pending: usize,
+    started: bool,
+    stdin: ChildStdin,
+}
+
+impl Client {
+    /// Starts the bot with the given command line, and waits until it
+    /// accepts the rules of the game.
+    pub fn spawn(command: &str) -> Result<Self, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("the bot command is empty")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("cannot start the bot '{program}': {e}"))?;
+
+        let stdin = child.stdin.take().ok_or("cannot write to the bot")?;
+        let stdout = child.stdout.take().ok_or("cannot read from the bot")?;
+        let (sender, messages) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if let Ok(message) = serde_json::from_str::<BotMessage>(&line)
+                    && sender.send(message).is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let mut client = Self {
+            child,
+            expected: None,
+            messages,
+            name: String::new(),
+            next: None,
+            pending: 0,
+            started: false,
+            stdin,
+        };
+        client.handshake()?;
+
+        Ok(client)
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Tells the bot about the piece which just entered the board, and asks
+    /// where to put it. Whenever the board isn't the one the bot expects, such
+    /// as after garbage rose, the bot starts over from the current board, and
+    /// from the combo and back to back of the game.
+    pub fn piece_spawned(&mut self, board: &Board, next_piece: Option<&Piece>, attack: &Attack) {
+        let Some(piece) = board.get_piece() else {
+            return;
+        };
+        let current = piece.get_tetromino().clone();
+        let next = next_piece.map(|p| p.get_tetromino().clone());
+
+        let in_sync = self
+            .expected
+            .take()
+            .is_some_and(|(text, tetromino)| text == board.to_text() && tetromino == current);
+        if in_sync {
+            if let Some(next) = next.as_ref() {
+                self.send(&FrontendMessage::NewPiece {
+                    piece: next.to_char(),
+                });
+            }
+        } else {
+            if self.started {
+                self.send(&FrontendMessage::Stop);
+            }
+            self.send(&start_message(board, &current, next.as_ref(), attack));
+            self.started = true;
+        }
+
+        self.next = next;
+        self.send(&FrontendMessage::Suggest);
+        self.pending += 1;
+    }
+
+    /// Returns the inputs leading to the move suggested by the bot, once the
+    /// suggestion for the active piece arrived. The first suggested move
+    /// reachable on the board is played, and when none is, the piece simply
+    /// drops where it is.
+    pub fn get_inputs(&mut self, board: &Board) -> Option<Vec<Input>> {
+        let mut moves = None;
+        while let Ok(message) = self.messages.try_recv() {
+            if let BotMessage::Suggestion { moves: suggested } = message {
+                // the suggestions of the pieces gone already are stale
+                self.pending = self.pending.saturating_sub(1);
+                moves = (self.pending == 0).then_some(suggested);
+            }
+        }
+        let moves = moves?;
+        let piece = board.get_piece()?;
+
+        let placements = board.legal_placements(piece);
+        let chosen = moves.into_iter().find_map(|mv| {
+            let cells = mv.location.get_cells()?;
+            let placement = placements.iter().find(|p| p.cells == cells)?;
+            Some((mv, placement))
+        });
+        let Some((mv, placement)) = chosen else {
+            return Some(Vec::new());
+        };
+
+        self.send(&FrontendMessage::Play { mv });
+        self.expected = bot::play(board, placement)
+            .zip(self.next.clone())
+            .map(|((after, _), next)| (after.to_text(), next));
+
+        Some(placement.inputs.clone())
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.send(&FrontendMessage::Quit);
+
+        let started = Instant::now();
+        while started.elapsed() < QUIT_TIMEOUT {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+// Private functions
+impl Client {
+    fn handshake(&mut self) -> Result<(), String> {
+        match self.receive_blocking()? {
+            BotMessage::Info {
+                name,
+                version,
+                author,
+            } => self.name = format!("{name} {version} by {author}"),
+            message => return Err(format!("unexpected message from the bot: {message:?}")),
+        }
+
+        self.send(&FrontendMessage::Rules);
+        match self.receive_blocking()? {
+            BotMessage::Ready => Ok(()),
+            BotMessage::Error { reason } => Err(format!("the bot rejected the rules: {reason}")),
+            message => Err(format!("unexpected message from the bot: {message:?}")),
+        }
+    }
+
+    fn receive_blocking(&mut self) -> Result<BotMessage, String> {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.messages.recv_timeout(timeout) {
+                Ok(BotMessage::Unknown) => continue,
+                Ok(message) => return Ok(message),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(String::from("the bot did not answer in time"));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(String::from("the bot exited"));
+                }
+            }
+        }
+    }
+
+    /// Writes a message to the bot. A bot which went away simply stops
+    /// suggesting moves, so write errors are ignored.
+    fn send(&mut self, message: &FrontendMessage) {
+        if let Ok(line) = serde_json::to_string(message) {
+            let _ = writeln!(self.stdin, "{line}");
+            let _ = self.stdin.flush();
+        }
+    }
+}
+
+fn start_message(
+    board: &Board,
+    current: &Tetromino,
+    next: Option<&Tetromino>,
+    attack: &Attack,
+) -> FrontendMessage {
+    let text = board.to_text();
+    let mut rows = text
+        .lines()
+        .rev()
+        .map(|line| {
+            let mut row = [None; BOARD_WIDTH];
+            for (cell, c) in row.iter_mut().zip(line.chars()) {
+                *cell = match Cell::try_from(c) {
+                    Ok(Cell::Black) | Err(_) => None,
+                    Ok(Cell::Gray) => Some('G'),
+                    Ok(_) => Some(c),
+                };
+            }
+            row
+        })
+        .collect::<Vec<[Option<char>; BOARD_WIDTH]>>();
+    rows.resize(TBP_BOARD_HEIGHT, [None; BOARD_WIDTH]);
+
+    FrontendMessage::Start {
+        hold: None,
+        queue: std::iter::once(current)
+            .chain(next)
+            .map(Tetromino::to_char)
+            .collect(),
+        combo: u32::try_from(attack.get_combo()).unwrap_or(u32::MAX),
+        back_to_back: attack.is_back_to_back(),
+        board: rows,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::LockResult;
+
+    /// A bot always suggesting the I piece flat in the bottom left corner.
+    const STUB_BOT: &str = r#"
+echo '{"type":"info","name":"stub","version":"1.0","author":"textris","features":[]}'
+while read -r line; do
+    case "$line" in
+        *'"rules"'*) echo '{"type":"ready"}' ;;
+        *'"suggest"'*) echo '{"type":"suggestion","moves":[{"location":{"type":"I","orientation":"north","x":1,"y":0},"spin":"none"}]}' ;;
+        *'"quit"'*) exit 0 ;;
+    esac
+done
+"#;
+
+    #[test]
+    fn message_format() {
+        let mv = Move {
+            location: Location {
+                piece: 'T',
+                orientation: String::from("south"),
+                x: 4,
+                y: 1,
+            },
+            spin: String::from("none"),
+        };
+        let line = serde_json::to_string(&FrontendMessage::Play { mv: mv.clone() }).unwrap();
+        assert_eq!(
+            r#"{"type":"play","move":{"location":{"type":"T","orientation":"south","x":4,"y":1},"spin":"none"}}"#,
+            line
+        );
+        assert_eq!(
+            r#"{"type":"new_piece","piece":"S"}"#,
+            serde_json::to_string(&FrontendMessage::NewPiece { piece: 'S' }).unwrap()
+        );
+
+        let message = r#"{"type":"suggestion","moves":[{"location":{"type":"T","orientation":"south","x":4,"y":1},"spin":"none"}],"move_info":{}}"#;
+        assert_eq!(
+            BotMessage::Suggestion { moves: vec![mv] },
+            serde_json::from_str(message).unwrap()
+        );
+        assert_eq!(
+            BotMessage::Unknown,
+            serde_json::from_str(r#"{"type":"something_else"}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn location_cells() {
+        let cells = |piece, orientation: &str, x, y| {
+            Location {
+                piece,
+                orientation: orientation.to_string(),
+                x,
+                y,
+            }
+            .get_cells()
+        };
+
+        assert_eq!(
+            Some(vec![(18, 4), (19, 3), (19, 4), (19, 5)]),
+            cells('T', "north", 4, 0)
+        );
+        assert_eq!(
+            Some(vec![(18, 3), (18, 4), (18, 5), (19, 4)]),
+            cells('T', "south", 4, 1)
+        );
+        assert_eq!(
+            Some(vec![(16, 0), (17, 0), (18, 0), (19, 0)]),
+            cells('I', "east", 0, 2)
+        );
+        assert_eq!(None, cells('I', "north", 0, 0));
+        assert_eq!(None, cells('O', "north", 9, 0));
+    }
+
+    #[test]
+    fn start_with_the_combo() {
+        let mut attack = Attack::default();
+        let tetris = LockResult {
+            lines: 4,
+            ..LockResult::default()
+        };
+        attack.compute(&tetris);
+        attack.compute(&tetris);
+
+        let message = start_message(&Board::new(), &Tetromino::T, None, &attack);
+        let FrontendMessage::Start {
+            combo,
+            back_to_back,
+            ..
+        } = message
+        else {
+            panic!("not a start message: {message:?}");
+        };
+        assert_eq!(2, combo);
+        assert!(back_to_back);
+    }
+
+    #[test]
+    fn play_with_stub_bot() {
+        let script =
+            std::env::temp_dir().join(format!("textris-stub-bot-{}.sh", std::process::id()));
+        std::fs::write(&script, STUB_BOT).unwrap();
+        let mut client = Client::spawn(&format!("sh {}", script.display())).unwrap();
+        assert_eq!("stub 1.0 by textris", client.get_name());
+
+        let mut board = Board::new();
+        board.add_piece(Piece::new(Tetromino::I, Tetromino::I.get_spawn_position()));
+        let next = Piece::new(Tetromino::O, Tetromino::O.get_spawn_position());
+        client.piece_spawned(&board, Some(&next), &Attack::default());
+
+        let started = Instant::now();
+        let inputs = loop {
+            if let Some(inputs) = client.get_inputs(&board) {
+                break inputs;
+            }
+            assert!(started.elapsed() < HANDSHAKE_TIMEOUT);
+        };
+        for input in inputs.into_iter().chain([Input::HardDrop]) {
+            board.apply_input(input);
+        }
+        assert_eq!(
+            vec![(19, 0), (19, 1), (19, 2), (19, 3)],
+            board.get_last_lock().cells
+        );
+
+        // the bot played its move, so it only hears of the next piece
+        board.add_piece(next);
+        assert!(client.expected.is_some());
+        client.piece_spawned(&board, None, &Attack::default());
+        assert!(client.expected.is_none());
+        assert_eq!(1, client.pending);
+
+        drop(client);
+        std::fs::remove_file(script).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crossterm::{
+    cursor::{Hide, Show},
+    event::{DisableFocusChange, EnableFocusChange},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+
+/// Whether the game is told line by line on the main screen, rather than
+/// drawn on the alternate screen.
+static LINEAR: AtomicBool = AtomicBool::new(false);
+
+/// Keeps the terminal set up for the game: raw mode, alternate screen, and
+/// hidden cursor. It's restored when the guard is dropped, which also covers
+/// the early returns on errors.
+pub struct Guard {
+    _private: (),
+}
+
+impl Guard {
+    /// Sets the terminal up, only in raw mode when the game is told line by
+    /// line.
+    pub fn enter(linear: bool) -> std::io::Result<Self> {
+        LINEAR.store(linear, Ordering::Relaxed);
+        enter()?;
+
+        Ok(Self { _private: () })
+    }
+
+    /// Restores the terminal, reporting the errors a drop would ignore.
+    pub fn leave(self) -> std::io::Result<()> {
+        std::mem::forget(self);
+        restore()
+    }
+
+    /// Restores the terminal and stops the process, as Ctrl+Z does outside of
+    /// raw mode, then sets the terminal up again once resumed.
+    pub fn suspend(&self) -> std::io::Result<()> {
+        restore()?;
+        #[cfg(unix)]
+        signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP)?;
+
+        enter()
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        let _ = restore();
+    }
+}
+
+/// Restores the terminal before a panic message gets printed, as it would be
+/// lost on the alternate screen otherwise.
+pub fn install_panic_hook() {
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        hook(info);
+    }));
+}
+
+/// The signals asking the game to end or to be suspended, as flags checked
+/// by the game loops.
+#[derive(Default)]
+pub struct Signals {
+    terminate: Arc<AtomicBool>,
+    suspend: Arc<AtomicBool>,
+}
+
+impl Signals {
+    /// Registers for SIGTERM and SIGHUP, which end the game, and SIGTSTP,
+    /// which suspends it. Only the Unix systems have them.
+    pub fn register() -> std::io::Result<Self> {
+        let signals = Self::default();
+
+        #[cfg(unix)]
+        {
+            use signal_hook::{
+                consts::{SIGHUP, SIGTERM, SIGTSTP},
+                flag,
+            };
+
+            flag::register(SIGTERM, Arc::clone(&signals.terminate))?;
+            flag::register(SIGHUP, Arc::clone(&signals.terminate))?;
+            flag::register(SIGTSTP, Arc::clone(&signals.suspend))?;
+        }
+
+        Ok(signals)
+    }
+
+    pub fn take_terminate(&self) -> bool {
+        self.terminate.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn take_suspend(&self) -> bool {
+        self.suspend.swap(false, Ordering::Relaxed)
+    }
+}
+
+fn enter() -> std::io::Result<()> {
+    enable_raw_mode()?;
+
+    let mut stdout = std::io::stdout();
+    if !LINEAR.load(Ordering::Relaxed) {
+        stdout.execute(EnterAlternateScreen)?.execute(Hide)?;
+    }
+    stdout.execute(EnableFocusChange)?;
+
+    Ok(())
+}
+
+fn restore() -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    stdout.execute(DisableFocusChange)?;
+    if !LINEAR.load(Ordering::Relaxed) {
+        stdout.execute(Show)?.execute(LeaveAlternateScreen)?;
+    }
+
+    disable_raw_mode()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn signals_are_taken_once() {
+        use signal_hook::{
+            consts::{SIGTERM, SIGTSTP},
+            low_level::raise,
+        };
+
+        let signals = Signals::register().unwrap();
+        assert!(!signals.take_suspend());
+
+        raise(SIGTSTP).unwrap();
+        assert!(signals.take_suspend());
+        assert!(!signals.take_suspend());
+        assert!(!signals.take_terminate());
+
+        raise(SIGTERM).unwrap();
+        assert!(signals.take_terminate());
+        assert!(!signals.take_terminate());
+    }
+}
@@ -0,0 +1,271 @@
+use std::str::FromStr;
+
+use crate::{context::ASSET_PATH, pieces::Cell};
+
+const THEMES_DIR: &str = "themes";
+
+/// The theme used unless another one is picked, the colors textris always had.
+const DEFAULT_THEME: &str = include_str!("../res/themes/textris.txt");
+
+/// A 24-bit color, written `#rrggbb` in theme files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid color '{s}', expected #rrggbb");
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .ok_or_else(invalid)?;
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                .ok_or_else(invalid)
+        };
+
+        Ok(Self(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl Rgb {
+    /// Returns the closest color of the 256 colors palette, either from its
+    /// 6x6x6 color cube or from its gray ramp.
+    pub fn to_ansi(self) -> u8 {
+        let Self(r, g, b) = self;
+        let level = |c: u8| if c < 48 { 0 } else { (c as u16 - 35) / 40 } as u8;
+        let value = |level: u8| if level == 0 { 0 } else { level * 40 + 55 };
+        let cube = (level(r), level(g), level(b));
+
+        let average = (r as u16 + g as u16 + b as u16) / 3;
+        let gray = ((average.saturating_sub(3)) / 10).min(23) as u8;
+        let gray_value = gray * 10 + 8;
+
+        let distance = |(cr, cg, cb): (u8, u8, u8)| {
+            [(r, cr), (g, cg), (b, cb)]
+                .iter()
+                .map(|&(a, b)| (a as i32 - b as i32).pow(2))
+                .sum::<i32>()
+        };
+        let cube_distance = distance((value(cube.0), value(cube.1), value(cube.2)));
+        if distance((gray_value, gray_value, gray_value)) < cube_distance {
+            232 + gray
+        } else {
+            16 + 36 * cube.0 + 6 * cube.1 + cube.2
+        }
+    }
+
+    /// Tells whether dark text reads better than light text on this color.
+    pub fn is_light(self) -> bool {
+        let Self(r, g, b) = self;
+        299 * r as u32 + 587 * g as u32 + 114 * b as u32 > 150_000
+    }
+}
+
+/// How the cells of one kind look: their color, and the pattern drawn on
+/// them, two characters wide, telling them apart without colors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Look {
+    pub color: Rgb,
+    pub pattern: Option<String>,
+}
+
+impl FromStr for Look {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let color = words.next().ok_or("the color is missing")?.parse()?;
+        let pattern = words.next().map(String::from);
+        if let Some(pattern) = pattern.as_ref()
+            && pattern.chars().count() != 2
+        {
+            return Err(format!(
+                "invalid pattern '{pattern}', expected two characters"
+            ));
+        }
+        if words.next().is_some() {
+            return Err(format!(
+                "invalid look '{s}', expected a color and a pattern"
+            ));
+        }
+
+        Ok(Self { color, pattern })
+    }
+}
+
+/// The colors and patterns of the board, as read from a theme file: one line
+/// per kind of cell, named after its board file character, plus the walls and
+/// the background of the well.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    background: Look,
+    wall: Look,
+    cells: Vec<(char, Look)>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::parse(DEFAULT_THEME).expect("the default theme is valid")
+    }
+}
+
+impl Theme {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut background = None;
+        let mut wall = None;
+        let mut cells = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once(':') {
+                // the name is only there for the readers of the file
+                Some(("name", _)) => {}
+                Some(("background", value)) => background = Some(value.parse()?),
+                Some(("wall", value)) => wall = Some(value.parse()?),
+                Some((key, value)) if key.len() == 1 => {
+                    let c = key.chars().next().unwrap_or_default();
+                    let cell = Cell::try_from(c)?;
+                    if cell == Cell::Black {
+                        return Err(String::from("the empty cells are the background"));
+                    }
+                    cells.push((c, value.parse()?));
+                }
+                _ => return Err(format!("invalid line '{line}'")),
+            }
+        }
+
+        for c in "IJLOSTZX".chars() {
+            if !cells.iter().any(|(cell, _)| *cell == c) {
+                return Err(format!("the look of '{c}' is missing"));
+            }
+        }
+
+        Ok(Self {
+            background: background.ok_or("the background is missing")?,
+            wall: wall.ok_or("the wall is missing")?,
+            cells,
+        })
+    }
+
+    /// Loads a theme file, or the theme with the given name from the themes
+    /// library.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = if name.contains(['/', '\\']) || name.ends_with(".txt") {
+            name.to_string()
+        } else {
+            format!("{ASSET_PATH}/{THEMES_DIR}/{name}.txt")
+        };
+        let text = std::fs::read_to_string(&path).map_err(|_| {
+            format!(
+                "unknown theme '{name}', available themes: {}",
+                Self::list_themes().join(", ")
+            )
+        })?;
+
+        Self::parse(&text).map_err(|e| format!("invalid theme '{name}': {e}"))
+    }
+
+    pub fn list_themes() -> Vec<String> {
+        let mut names = std::fs::read_dir(format!("{ASSET_PATH}/{THEMES_DIR}"))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name().into_string().ok()?;
+                        name.strip_suffix(".txt").map(String::from)
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        names.sort();
+
+        names
+    }
+
+    pub fn get_look(&self, cell: Cell) -> &Look {
+        if cell == Cell::Black {
+            return &self.background;
+        }
+
+        let c = cell.to_char();
+        self.cells
+            .iter()
+            .find(|(cell, _)| *cell == c)
+            .map(|(_, look)| look)
+            .unwrap_or(&self.background)
+    }
+
+    pub fn get_wall(&self) -> &Look {
+        &self.wall
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_colors() {
+        assert_eq!(Ok(Rgb(0, 240, 16)), "#00f010".parse());
+        assert!("00f010".parse::<Rgb>().is_err());
+        assert!("#00f01".parse::<Rgb>().is_err());
+
+        assert_eq!(16, Rgb(0, 0, 0).to_ansi());
+        assert_eq!(231, Rgb(255, 255, 255).to_ansi());
+        assert_eq!(196, Rgb(255, 0, 0).to_ansi());
+        assert_eq!(244, Rgb(128, 128, 128).to_ansi());
+        assert!(Rgb(248, 216, 0).is_light());
+        assert!(!Rgb(0, 88, 248).is_light());
+    }
+
+    #[test]
+    fn builtin_themes() {
+        let names = Theme::list_themes();
+        for name in [
+            "textris",
+            "guideline",
+            "nes",
+            "monochrome",
+            "high-contrast",
+            "deuteranopia",
+            "protanopia",
+        ] {
+            assert!(names.contains(&name.to_string()), "{name}");
+            Theme::load(name).unwrap();
+        }
+        assert_eq!(Theme::default(), Theme::load("textris").unwrap());
+    }
+
+    #[test]
+    fn color_blind_themes_have_patterns() {
+        for name in ["monochrome", "deuteranopia", "protanopia"] {
+            let theme = Theme::load(name).unwrap();
+            let mut patterns = "IJLOSTZ"
+                .chars()
+                .map(|c| theme.get_look(Cell::try_from(c).unwrap()).pattern.clone())
+                .collect::<Vec<Option<String>>>();
+            patterns.sort();
+            patterns.dedup();
+            assert!(patterns.iter().all(Option::is_some), "{name}");
+            assert_eq!(7, patterns.len(), "{name}");
+        }
+    }
+
+    #[test]
+    fn invalid_theme() {
+        let text = "background: #000000\nwall: #808080\nI: #00ffff\n";
+        assert_eq!(
+            Err(String::from("the look of 'J' is missing")),
+            Theme::parse(text)
+        );
+        assert!(Theme::parse("background: #000000 abc").is_err());
+        assert!(Theme::load("no-such-theme").is_err());
+    }
+}
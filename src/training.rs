@@ -0,0 +1,216 @@
+use std::str::FromStr;
+
+use crate::{
+    board::{Board, BOARD_WIDTH},
+    context::ASSET_PATH,
+    pieces::Tetromino,
+    puzzle::Challenge,
+};
+
+pub const OPENER_DEFAULT: &str = "empty";
+pub const PERFECT_CLEAR_HEIGHT: usize = 4;
+
+const OPENERS_DIR: &str = "openers";
+
+/// Order of the pieces dealt on every perfect clear attempt.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BagOrder {
+    /// The sequence recommended by the opener file.
+    Opener,
+    Random,
+    Fixed(Vec<Tetromino>),
+}
+
+impl FromStr for BagOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "opener" => Ok(Self::Opener),
+            "random" => Ok(Self::Random),
+            _ => s
+                .chars()
+                .map(Tetromino::try_from)
+                .collect::<Result<Vec<Tetromino>, String>>()
+                .map(Self::Fixed)
+                .map_err(|e| format!("invalid bag '{s}': {e}")),
+        }
+    }
+}
+
+/// Perfect clear practice: every attempt starts from the field of the opener,
+/// and ends either with a perfect clear, or when the stack grows past the
+/// perfect clear height, or when enough pieces were placed to fill it.
+pub struct Training {
+    attempts: usize,
+    bag: Option<Vec<Tetromino>>,
+    name: String,
+    opener: Challenge,
+    pieces_placed: usize,
+    successes: usize,
+}
+
+impl Training {
+    pub fn new(name: &str, opener: Challenge, bag_order: BagOrder) -> Self {
+        let bag = match bag_order {
+            BagOrder::Opener => Some(opener.pieces.clone()),
+            BagOrder::Random => None,
+            BagOrder::Fixed(pieces) => Some(pieces),
+        };
+
+        Self {
+            attempts: 0,
+            bag,
+            name: name.to_uppercase(),
+            opener,
+            pieces_placed: 0,
+            successes: 0,
+        }
+    }
+
+    /// Loads the opener with the given name from the openers library.
+    pub fn load_opener(name: &str) -> Result<Challenge, String> {
+        let path = format!("{ASSET_PATH}/{OPENERS_DIR}/{name}.txt");
+        let text = std::fs::read_to_string(&path).map_err(|_| {
+            format!(
+                "unknown opener '{name}', available openers: {}",
+                Self::list_openers().join(", ")
+            )
+        })?;
+
+        Challenge::parse(&text).map_err(|e| format!("invalid opener '{name}': {e}"))
+    }
+
+    pub fn list_openers() -> Vec<String> {
+        let mut names = std::fs::read_dir(format!("{ASSET_PATH}/{OPENERS_DIR}"))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name().into_string().ok()?;
+                        name.strip_suffix(".txt").map(String::from)
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .unwrap_or_default();
+        names.sort();
+
+        names
+    }
+
+    pub fn get_board(&self) -> Board {
+        self.opener.board.clone()
+    }
+
+    /// Returns the fixed bag to deal from, or none for random bags.
+    pub fn get_bag(&self) -> Option<&Vec<Tetromino>> {
+        self.bag.as_ref()
+    }
+
+    /// Accounts for a piece locked on the board. Returns whether the attempt
+    /// succeeded, or none while the attempt is still going on.
+    pub fn record_lock(&mut self, board: &Board) -> Option<bool> {
+        self.pieces_placed += 1;
+
+        let result = if board.get_last_lock().perfect_clear {
+            Some(true)
+        } else if board.get_stack_height() > PERFECT_CLEAR_HEIGHT
+            || self.pieces_placed >= self.get_max_pieces()
+        {
+            Some(false)
+        } else {
+            None
+        };
+
+        if let Some(success) = result {
+            self.attempts += 1;
+            self.successes += usize::from(success);
+            self.pieces_placed = 0;
+        }
+
+        result
+    }
+
+    /// Accounts for a failed attempt, as the next piece couldn't even enter
+    /// the board.
+    pub fn record_top_out(&mut self) {
+        self.attempts += 1;
+        self.pieces_placed = 0;
+    }
+
+    /// Number of pieces that fill up the perfect clear height of the opener.
+    pub fn get_max_pieces(&self) -> usize {
+        let filled = self.opener.board.count_cells();
+
+        (PERFECT_CLEAR_HEIGHT * BOARD_WIDTH).saturating_sub(filled) / 4
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_attempts(&self) -> usize {
+        self.attempts
+    }
+
+    pub fn get_successes(&self) -> usize {
+        self.successes
+    }
+
+    pub fn get_pieces_placed(&self) -> usize {
+        self.pieces_placed
+    }
+
+    pub fn get_success_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            0.0
+        } else {
+            self.successes as f64 * 100.0 / self.attempts as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bag_order() {
+        assert_eq!(Ok(BagOrder::Opener), "opener".parse());
+        assert_eq!(Ok(BagOrder::Random), "random".parse());
+        assert_eq!(
+            Ok(BagOrder::Fixed(vec![Tetromino::L, Tetromino::O])),
+            "LO".parse()
+        );
+        assert!("LQ".parse::<BagOrder>().is_err());
+    }
+
+    #[test]
+    fn bundled_openers_load() {
+        let openers = Training::list_openers();
+        assert!(openers.contains(&String::from(OPENER_DEFAULT)));
+
+        for name in openers {
+            let training = Training::new(
+                &name,
+                Training::load_opener(&name).unwrap(),
+                BagOrder::Opener,
+            );
+            assert!(training.get_max_pieces() > 0);
+            assert!(training.get_board().get_stack_height() <= PERFECT_CLEAR_HEIGHT);
+        }
+    }
+
+    #[test]
+    fn top_out_fails_the_attempt() {
+        let mut training = Training::new(
+            OPENER_DEFAULT,
+            Training::load_opener(OPENER_DEFAULT).unwrap(),
+            BagOrder::Opener,
+        );
+        training.record_top_out();
+
+        assert_eq!(1, training.get_attempts());
+        assert_eq!(0, training.get_successes());
+    }
+}
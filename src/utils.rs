@@ -1,3 +1,24 @@
+use std::{collections::VecDeque, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+/// The game logic advances in steps of one frame, sixty times per second.
+pub const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+
+/// Garbage rows sent by line clears, by number of lines cleared at once.
+const ATTACK_LINES: [usize; 5] = [0, 0, 1, 2, 4];
+/// Garbage rows sent by T-spins, by number of lines cleared at once.
+const ATTACK_TSPIN: [usize; 4] = [0, 2, 4, 6];
+/// Extra garbage rows sent by combos, by number of clears in a row so far.
+const ATTACK_COMBO: [usize; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const ATTACK_BACK_TO_BACK: usize = 1;
+const ATTACK_PERFECT_CLEAR: usize = 10;
+
+/// Frames the incoming garbage waits before it may rise into the well.
+pub const GARBAGE_DELAY_FRAMES: u32 = 30;
+/// Most garbage rows rising at once, the rest waits for the next piece.
+pub const GARBAGE_RISE_MAX: usize = 8;
+
 #[derive(Clone)]
 pub struct Position {
     pub row: isize,
@@ -10,7 +31,7 @@ impl Position {
     }
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub enum Direction {
     Up,
     Down,
@@ -23,7 +44,19 @@ pub enum Rotation {
     CounterClockwise,
 }
 
-#[derive(Default)]
+/// A single action of the player on the active piece.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Input {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+}
+
+#[derive(Clone, Default)]
 pub struct Score {
     pub lines_destroyed: usize,
     pub points: u64,
@@ -36,6 +69,134 @@ impl Score {
     }
 }
 
+/// Computes the garbage rows sent by every lock, following the guideline
+/// attack table, with back to back and combo bonuses.
+#[derive(Clone, Default)]
+pub struct Attack {
+    back_to_back: bool,
+    combo: usize,
+}
+
+impl Attack {
+    pub fn compute(&mut self, lock: &LockResult) -> usize {
+        if lock.lines == 0 {
+            self.combo = 0;
+            return 0;
+        }
+
+        let mut rows = if lock.tspin {
+            ATTACK_TSPIN[lock.lines.min(3)]
+        } else {
+            ATTACK_LINES[lock.lines.min(4)]
+        };
+
+        // tetrises and T-spins are difficult clears, chaining them pays extra
+        let difficult = lock.lines == 4 || lock.tspin;
+        if difficult && self.back_to_back {
+            rows += ATTACK_BACK_TO_BACK;
+        }
+        self.back_to_back = difficult;
+
+        rows += ATTACK_COMBO[self.combo.min(ATTACK_COMBO.len() - 1)];
+        self.combo += 1;
+
+        if lock.perfect_clear {
+            rows += ATTACK_PERFECT_CLEAR;
+        }
+
+        rows
+    }
+
+    pub fn get_combo(&self) -> usize {
+        self.combo
+    }
+
+    pub fn is_back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+}
+
+/// Garbage sent by the opponent, waiting to rise into the well. Every attack
+/// waits a little before it's ready, and clearing lines cancels the waiting
+/// garbage before anything is sent back.
+#[derive(Clone, Default)]
+pub struct GarbageQueue {
+    /// Garbage rows of every attack, with the frames left before they're ready.
+    pending: VecDeque<(usize, u32)>,
+}
+
+impl GarbageQueue {
+    pub fn receive(&mut self, rows: usize) {
+        if rows > 0 {
+            self.pending.push_back((rows, GARBAGE_DELAY_FRAMES));
+        }
+    }
+
+    pub fn step_frame(&mut self) {
+        for (_, frames) in self.pending.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+    }
+
+    /// Cancels the waiting garbage with an attack, oldest first. Returns the
+    /// rows left to send to the opponent.
+    pub fn cancel(&mut self, mut attack: usize) -> usize {
+        while attack > 0
+            && let Some((rows, _)) = self.pending.front_mut()
+        {
+            let cancelled = attack.min(*rows);
+            attack -= cancelled;
+            *rows -= cancelled;
+            if *rows == 0 {
+                self.pending.pop_front();
+            }
+        }
+
+        attack
+    }
+
+    /// Takes the garbage rows ready to rise into the well, at most
+    /// `GARBAGE_RISE_MAX` of them.
+    pub fn take_ready(&mut self) -> usize {
+        let mut taken = 0;
+
+        while let Some((rows, 0)) = self.pending.front_mut() {
+            let rising = (*rows).min(GARBAGE_RISE_MAX - taken);
+            taken += rising;
+            *rows -= rising;
+            if *rows > 0 {
+                break;
+            }
+            self.pending.pop_front();
+        }
+
+        taken
+    }
+
+    pub fn get_total(&self) -> usize {
+        self.pending
+            .iter()
+            .fold(0, |total, (rows, _)| total.saturating_add(*rows))
+    }
+
+    pub fn get_ready(&self) -> usize {
+        self.pending
+            .iter()
+            .filter(|(_, frames)| *frames == 0)
+            .fold(0, |total, (rows, _)| total.saturating_add(*rows))
+    }
+}
+
+/// Describes what happened when the last piece was locked on the board.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LockResult {
+    /// Board cells taken by the piece, before the lines were cleared.
+    pub cells: Vec<(usize, usize)>,
+    pub lines: usize,
+    pub tspin: bool,
+    pub perfect_clear: bool,
+}
+
 /// Translates the piece coordinates into board coordinates, by adding to the
 /// piece coordinates the position (top-left) of the piece relative to the board.
 ///
@@ -70,3 +231,99 @@ pub fn to_usize(col: isize, row: isize) -> (usize, usize) {
 pub fn is_within_bounds(val: isize, min: isize, max: isize) -> bool {
     val >= min && val < max
 }
+
+/// Splits a text into lines of at most the given width, between words. A word
+/// longer than the width gets a line of its own.
+pub fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+
+    lines
+}
+
+/// Formats a duration as minutes, seconds and hundredths, e.g. "01:23.45".
+pub fn format_duration(duration: Duration) -> String {
+    let centis = duration.as_millis() / 10;
+
+    format!("{:02}:{:02}.{:02}", centis / 6000, centis / 100 % 60, centis % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock(lines: usize, tspin: bool) -> LockResult {
+        LockResult {
+            lines,
+            tspin,
+            ..LockResult::default()
+        }
+    }
+
+    #[test]
+    fn attack_table() {
+        let mut attack = Attack::default();
+
+        assert_eq!(0, attack.compute(&lock(1, false)));
+        assert_eq!(0, attack.compute(&lock(0, false)));
+        assert_eq!(4, attack.compute(&lock(4, false)));
+        assert_eq!(0, attack.compute(&lock(0, false)));
+        // back to back T-spin double
+        assert_eq!(5, attack.compute(&lock(2, true)));
+        // combo of two clears, the double breaks the back to back
+        assert_eq!(1, attack.compute(&lock(2, false)));
+        assert!(!attack.is_back_to_back());
+        assert_eq!(2, attack.get_combo());
+
+        let perfect_clear = LockResult {
+            perfect_clear: true,
+            ..lock(2, false)
+        };
+        assert_eq!(11, Attack::default().compute(&perfect_clear));
+    }
+
+    #[test]
+    fn garbage_queue() {
+        let mut queue = GarbageQueue::default();
+        queue.receive(3);
+        queue.receive(6);
+
+        assert_eq!(0, queue.cancel(4));
+        assert_eq!(5, queue.get_total());
+        assert_eq!(0, queue.take_ready());
+
+        for _ in 0..GARBAGE_DELAY_FRAMES {
+            queue.step_frame();
+        }
+        queue.receive(4);
+        assert_eq!(5, queue.get_ready());
+        assert_eq!(5, queue.take_ready());
+        assert_eq!(4, queue.get_total());
+
+        queue.receive(10);
+        for _ in 0..GARBAGE_DELAY_FRAMES {
+            queue.step_frame();
+        }
+        assert_eq!(GARBAGE_RISE_MAX, queue.take_ready());
+        assert_eq!(6, queue.get_total());
+    }
+
+    #[test]
+    fn wrap_words_within_width() {
+        assert_eq!(
+            vec!["T-SPIN, CLEARS 2 LINES,", "FILLS WELL, CREATES 1", "HOLE"],
+            wrap_words("T-SPIN, CLEARS 2 LINES, FILLS WELL, CREATES 1 HOLE", 24)
+        );
+        assert_eq!(vec!["FLATTENS", "STACK"], wrap_words("FLATTENS STACK", 5));
+        assert!(wrap_words("", 24).is_empty());
+    }
+}
@@ -0,0 +1,568 @@
+use std::time::{Duration, Instant};
+
+use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+use crate::{
+    animation::Animations,
+    board::{Board, BOARD_HEIGHT},
+    bot::Bot,
+    context::Context,
+    garbage::GarbageGenerator,
+    mode::SURVIVAL_HOLE_CHANGE_PROBABILITY,
+    pause::Item,
+    pieces::Cell,
+    player::Player,
+    render::{self, Line, Tile, View},
+    utils::{Attack, Direction, GarbageQueue, Input, Score, FRAME_DURATION},
+};
+
+/// Terminal columns taken by each player, board and panel included.
+pub const SIDE_COLUMNS: u16 = 50;
+/// Terminal columns taken by a board and its garbage meter, the panel of the
+/// player comes next.
+pub const BOARD_COLUMNS: u16 = 28;
+
+/// Time the result of a round stays on screen, before the next round starts.
+pub const ROUND_PAUSE: Duration = Duration::from_secs(3);
+
+/// Keys of one player, letters are matched regardless of their case.
+pub struct Keymap {
+    left: KeyCode,
+    right: KeyCode,
+    soft_drop: KeyCode,
+    hard_drop: KeyCode,
+    rotate_left: KeyCode,
+    rotate_right: KeyCode,
+    /// Key names shown in the panel, for moving, dropping and rotating.
+    labels: [&'static str; 3],
+}
+
+pub const KEYMAPS: [Keymap; 2] = [
+    Keymap {
+        left: KeyCode::Char('a'),
+        right: KeyCode::Char('d'),
+        soft_drop: KeyCode::Char('s'),
+        hard_drop: KeyCode::Char('w'),
+        rotate_left: KeyCode::Char('q'),
+        rotate_right: KeyCode::Char('e'),
+        labels: ["A / D", "S / W", "Q / E"],
+    },
+    Keymap {
+        left: KeyCode::Left,
+        right: KeyCode::Right,
+        soft_drop: KeyCode::Down,
+        hard_drop: KeyCode::Up,
+        rotate_left: KeyCode::Char('z'),
+        rotate_right: KeyCode::Char('x'),
+        labels: ["LEFT / RIGHT", "DOWN / UP", "Z / X"],
+    },
+];
+
+/// Keys of the single player of a networked match, the same as in the other modes.
+pub const ONLINE_KEYMAP: Keymap = Keymap {
+    left: KeyCode::Left,
+    right: KeyCode::Right,
+    soft_drop: KeyCode::Down,
+    hard_drop: KeyCode::Char(' '),
+    rotate_left: KeyCode::Char('z'),
+    rotate_right: KeyCode::Char('x'),
+    labels: ["LEFT / RIGHT", "DOWN / SPACE", "Z / X"],
+};
+
+impl Keymap {
+    pub fn get_input(&self, code: KeyCode) -> Option<Input> {
+        let code = match code {
+            KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+            code => code,
+        };
+
+        if code == self.left {
+            Some(Input::Left)
+        } else if code == self.right {
+            Some(Input::Right)
+        } else if code == self.soft_drop {
+            Some(Input::SoftDrop)
+        } else if code == self.hard_drop {
+            Some(Input::HardDrop)
+        } else if code == self.rotate_left {
+            Some(Input::RotateCounterClockwise)
+        } else if code == self.rotate_right {
+            Some(Input::RotateClockwise)
+        } else {
+            None
+        }
+    }
+}
+
+/// One of the two players of the match, with a board of their own.
+pub struct Side {
+    animations: Animations,
+    attack: Attack,
+    pub board: Board,
+    /// The computer playing this side, instead of the keyboard.
+    pub bot: Option<Bot>,
+    pub garbage: GarbageQueue,
+    generator: GarbageGenerator,
+    gravity: f64,
+    pub keymap: &'static Keymap,
+    lines_sent: usize,
+    player: Player,
+    pub topped_out: bool,
+    pub wins: usize,
+}
+
+impl Side {
+    pub fn new(keymap: &'static Keymap) -> Self {
+        Self {
+            animations: Animations::default(),
+            attack: Attack::default(),
+            board: Board::new(),
+            bot: None,
+            garbage: GarbageQueue::default(),
+            generator: GarbageGenerator::new(SURVIVAL_HOLE_CHANGE_PROBABILITY),
+            gravity: 0.0,
+            keymap,
+            lines_sent: 0,
+            player: Player::new(),
+            topped_out: false,
+            wins: 0,
+        }
+    }
+
+    /// Starts a new round from an empty board, only the wins and the
+    /// computer are kept.
+    pub fn reset(&mut self) {
+        *self = Self {
+            bot: self.bot.take(),
+            wins: self.wins,
+            ..Self::new(self.keymap)
+        };
+    }
+
+    pub fn spawn_piece(&mut self) {
+        if !self.board.has_piece()
+            && let Some(piece) = self.player.get_piece()
+        {
+            if !self.board.add_piece(piece) {
+                self.top_out();
+            }
+            if let Some(bot) = self.bot.as_mut() {
+                bot.piece_spawned(&self.board, self.player.next_piece.as_ref(), &self.attack);
+            }
+        }
+    }
+
+    /// Plays the next input of the computer, if it's time to. Returns the
+    /// garbage rows to send to the opponent.
+    pub fn play_bot(&mut self) -> usize {
+        let input = self
+            .bot
+            .as_mut()
+            .and_then(|bot| bot.next_input(&self.board, self.player.next_piece.as_ref()));
+
+        input.map_or(0, |input| self.apply_input(input))
+    }
+
+    /// Applies an input of the player, and returns the garbage rows to send to
+    /// the opponent.
+    pub fn apply_input(&mut self, input: Input) -> usize {
+        let had_piece = self.board.has_piece();
+        let score = self.board.apply_input(input);
+
+        if had_piece && !self.board.has_piece() {
+            self.piece_locked(score)
+        } else {
+            0
+        }
+    }
+
+    /// Advances the gravity by one frame, and returns the garbage rows to send
+    /// to the opponent.
+    pub fn step_frame(&mut self) -> usize {
+        self.garbage.step_frame();
+        if !self.board.has_piece() {
+            return 0;
+        }
+
+        self.gravity += self.player.get_gravity();
+        while self.gravity >= 1.0 {
+            self.gravity -= 1.0;
+
+            let (moved, score) = self.board.move_piece(Direction::Down);
+            if !moved {
+                return self.piece_locked(score);
+            }
+        }
+
+        0
+    }
+
+    fn piece_locked(&mut self, score: Score) -> usize {
+        for event in self.board.take_events() {
+            self.animations.start(event);
+        }
+
+        // clearing lines cancels the incoming garbage first
+        let attack = self.attack.compute(self.board.get_last_lock());
+        let sent = self.garbage.cancel(attack);
+
+        self.gravity = 0.0;
+        self.lines_sent += sent;
+
+        // incoming garbage waits while the player keeps clearing lines
+        if score.lines_destroyed == 0 {
+            let rows = self.garbage.take_ready();
+            if rows > 0 && !self.board.insert_garbage(rows, self.generator.next_hole()) {
+                self.top_out();
+            }
+        }
+
+        self.player.increment_score(score);
+        self.spawn_piece();
+
+        sent
+    }
+
+    fn top_out(&mut self) {
+        self.topped_out = true;
+        self.animations.top_out();
+    }
+
+    /// Returns the lines of the board, with the meter of the incoming garbage
+    /// right beside the well.
+    pub fn board_lines(&self) -> Vec<Line> {
+        let total = self.garbage.get_total();
+        let ready = self.garbage.get_ready();
+
+        let mut tiles = render::board_tiles(&self.board);
+        self.animations.apply(&mut tiles);
+        let mut lines = render::well_lines(tiles);
+        for (row, line) in lines.iter_mut().enumerate() {
+            let height = BOARD_HEIGHT.saturating_sub(row);
+            let meter = if row >= BOARD_HEIGHT || height > total {
+                Tile::Blank
+            } else if height <= ready {
+                Tile::Cell(Cell::Red)
+            } else {
+                Tile::Cell(Cell::Orange)
+            };
+            line.push_tiles(vec![meter]);
+        }
+
+        lines
+    }
+
+    pub fn panel(&self, name: &str, wins_needed: usize) -> Vec<Line> {
+        let next_piece = self
+            .player
+            .next_piece
+            .as_ref()
+            .map(render::piece_lines)
+            .unwrap_or_default();
+
+        let panel = vec![
+            name.to_string(),
+            format!("WINS:     {} / {wins_needed}", self.wins),
+            String::new(),
+            String::from("NEXT PIECE:"),
+        ];
+        let mut lines = panel.into_iter().map(Line::from).collect::<Vec<Line>>();
+        lines.extend(next_piece);
+        lines.extend(
+            [
+                String::new(),
+                format!("LEVEL:    {}", self.player.level + 1),
+                format!("LINES:    {}", self.player.score.lines_destroyed),
+                format!("SENT:     {}", self.lines_sent),
+                format!("INCOMING: {}", self.garbage.get_total()),
+                format!("COMBO:    {}", self.attack.get_combo()),
+                format!(
+                    "B2B:      {}",
+                    if self.attack.is_back_to_back() {
+                        "ON"
+                    } else {
+                        "OFF"
+                    }
+                ),
+                String::new(),
+                format!("MOVE:     {}", self.keymap.labels[0]),
+                format!("DROP:     {}", self.keymap.labels[1]),
+                format!("ROTATE:   {}", self.keymap.labels[2]),
+            ]
+            .map(Line::from),
+        );
+
+        lines
+    }
+}
+
+/// Plays a match of two players sharing the keyboard, each one on a board of
+/// their own, until one of them wins the majority of the rounds. Returns the
+/// result of the match.
+pub fn game_loop(
+    context: &mut Context,
+    rounds: usize,
+    bot: Option<Bot>,
+) -> std::io::Result<Option<String>> {
+    let wins_needed = rounds / 2 + 1;
+    let mut sides = [Side::new(&KEYMAPS[0]), Side::new(&KEYMAPS[1])];
+    sides[1].bot = bot;
+    let mut round_ended: Option<Instant> = None;
+    let mut status = String::new();
+    let mut paused_for: Option<Duration> = None;
+    let mut last_frame = Instant::now();
+
+    loop {
+        if round_ended.is_none() {
+            sides.iter_mut().for_each(Side::spawn_piece);
+        }
+
+        if context.handle_signals()? {
+            return Ok(Some(format!(
+                "Match abandoned at {} - {}",
+                sides[0].wins, sides[1].wins
+            )));
+        }
+        if context.is_render_due() {
+            print_match(context, &sides, wins_needed, &status)?;
+        }
+
+        let deadline = sides
+            .iter()
+            .filter_map(|side| side.bot.as_ref()?.get_next_input_at())
+            .fold(last_frame + FRAME_DURATION, Instant::min);
+        if poll(context.get_idle_time(deadline))? {
+            let event = read()?;
+            if context.is_paused() {
+                // the hints only show in the single player modes
+                match context.pause_menu_event(&mut Board::new(), event)? {
+                    Some(Item::Restart) => {
+                        for side in &mut sides {
+                            side.reset();
+                            side.wins = 0;
+                        }
+                        round_ended = None;
+                        status.clear();
+                        context.resume();
+                    }
+                    Some(Item::Quit) => {
+                        return Ok(Some(format!(
+                            "Match abandoned at {} - {}",
+                            sides[0].wins, sides[1].wins
+                        )));
+                    }
+                    _ => {}
+                }
+            } else {
+                match event {
+                    Event::Resize(width, height) => context.resize(width, height),
+                    Event::FocusLost => context.pause(),
+                    Event::Key(KeyEvent {
+                        code: KeyCode::Char('z'),
+                        modifiers: KeyModifiers::CONTROL,
+                        kind: KeyEventKind::Press,
+                        ..
+                    }) => context.suspend()?,
+                    Event::Key(KeyEvent {
+                        code,
+                        kind: KeyEventKind::Press,
+                        ..
+                    }) => match code {
+                        KeyCode::Esc => {
+                            return Ok(Some(format!(
+                                "Match abandoned at {} - {}",
+                                sides[0].wins, sides[1].wins
+                            )));
+                        }
+                        KeyCode::Char('c') | KeyCode::Char('C') => context.pause(),
+                        KeyCode::Char('m') | KeyCode::Char('M') => context.mute_toggle(),
+                        KeyCode::Char('-') => context.volume_down(),
+                        KeyCode::Char('+') => context.volume_up(),
+                        _ if round_ended.is_none() => {
+                            for i in 0..sides.len() {
+                                if sides[i].bot.is_none()
+                                    && let Some(input) = sides[i].keymap.get_input(code)
+                                {
+                                    let attack = sides[i].apply_input(input);
+                                    sides[1 - i].garbage.receive(attack);
+                                }
+                            }
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+        }
+
+        // the game can't go on out of sight
+        if context.is_too_small() {
+            context.pause();
+        }
+        if context.is_paused() {
+            paused_for = context.get_pause_duration();
+            last_frame = Instant::now();
+            continue;
+        }
+        // the bots and the break between rounds skip the pause
+        if let Some(paused_for) = paused_for.take() {
+            for bot in sides.iter_mut().filter_map(|side| side.bot.as_mut()) {
+                bot.postpone(paused_for);
+            }
+            if let Some(ended) = round_ended.as_mut() {
+                *ended += paused_for;
+            }
+        }
+
+        if let Some(ended) = round_ended {
+            if ended.elapsed() >= ROUND_PAUSE {
+                if let Some(winner) = sides.iter().position(|side| side.wins >= wins_needed) {
+                    return Ok(Some(format!(
+                        "Player {} wins the match {} - {}",
+                        winner + 1,
+                        sides[winner].wins,
+                        sides[1 - winner].wins
+                    )));
+                }
+
+                sides.iter_mut().for_each(Side::reset);
+                round_ended = None;
+                status.clear();
+            }
+            last_frame = Instant::now();
+            continue;
+        }
+
+        for i in 0..sides.len() {
+            let attack = sides[i].play_bot();
+            sides[1 - i].garbage.receive(attack);
+        }
+
+        while last_frame.elapsed() >= FRAME_DURATION {
+            last_frame += FRAME_DURATION;
+            for i in 0..sides.len() {
+                let attack = sides[i].step_frame();
+                sides[1 - i].garbage.receive(attack);
+            }
+        }
+
+        if sides.iter().any(|side| side.topped_out) {
+            status = match (sides[0].topped_out, sides[1].topped_out) {
+                (true, true) => String::from("DRAW, BOTH PLAYERS TOPPED OUT"),
+                (true, false) => round_won(&mut sides[1], 2, wins_needed),
+                _ => round_won(&mut sides[0], 1, wins_needed),
+            };
+            round_ended = Some(Instant::now());
+        }
+    }
+}
+
+fn round_won(side: &mut Side, number: usize, wins_needed: usize) -> String {
+    side.wins += 1;
+
+    if side.wins >= wins_needed {
+        format!("PLAYER {number} WINS THE MATCH!")
+    } else {
+        format!("PLAYER {number} WINS THE ROUND")
+    }
+}
+
+fn print_match(
+    context: &mut Context,
+    sides: &[Side; 2],
+    wins_needed: usize,
+    status: &str,
+) -> std::io::Result<()> {
+    let mut columns = Vec::new();
+    // the menu hides both wells while paused
+    let menu = context.get_menu_lines();
+
+    for (i, side) in sides.iter().enumerate() {
+        let col = i as u16 * SIDE_COLUMNS;
+
+        if context.is_paused() {
+            columns.push((col, render::message_lines(&menu)));
+        } else {
+            columns.push((col, side.board_lines()));
+        }
+        columns.push((
+            col + BOARD_COLUMNS,
+            side.panel(&format!("PLAYER {}", i + 1), wins_needed),
+        ));
+    }
+
+    // the result of the round shows right below the boards
+    columns[0].1.extend([Line::default(), Line::from(status)]);
+
+    context.render(&View { columns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pieces::{Piece, Tetromino},
+        utils::GARBAGE_DELAY_FRAMES,
+    };
+
+    #[test]
+    fn keymaps() {
+        assert_eq!(Some(Input::Left), KEYMAPS[0].get_input(KeyCode::Char('A')));
+        assert_eq!(Some(Input::HardDrop), KEYMAPS[1].get_input(KeyCode::Up));
+        assert_eq!(None, KEYMAPS[0].get_input(KeyCode::Left));
+
+        // no key is shared between the players
+        for c in "wasdqezx".chars() {
+            let code = KeyCode::Char(c);
+            assert!(KEYMAPS[0].get_input(code).is_none() || KEYMAPS[1].get_input(code).is_none());
+        }
+    }
+
+    fn tetris_ready(side: &mut Side) {
+        side.board =
+            Board::from_text("X.........\nXXXXXXXXX.\nXXXXXXXXX.\nXXXXXXXXX.\nXXXXXXXXX.").unwrap();
+        assert!(side
+            .board
+            .add_piece(Piece::new(Tetromino::I, Tetromino::I.get_spawn_position())));
+        for _ in 0..5 {
+            side.apply_input(Input::Right);
+        }
+    }
+
+    #[test]
+    fn line_clears_send_garbage() {
+        let mut side = Side::new(&KEYMAPS[0]);
+        tetris_ready(&mut side);
+
+        assert_eq!(4, side.apply_input(Input::HardDrop));
+        assert_eq!(4, side.lines_sent);
+    }
+
+    #[test]
+    fn line_clears_cancel_incoming_garbage() {
+        let mut side = Side::new(&KEYMAPS[0]);
+        side.garbage.receive(3);
+        tetris_ready(&mut side);
+
+        assert_eq!(1, side.apply_input(Input::HardDrop));
+        assert_eq!(0, side.garbage.get_total());
+    }
+
+    #[test]
+    fn incoming_garbage_rises_after_delay() {
+        let mut side = Side::new(&KEYMAPS[0]);
+        side.garbage.receive(3);
+
+        side.spawn_piece();
+        side.apply_input(Input::HardDrop);
+        assert_eq!(0, side.board.count_garbage_rows());
+
+        for _ in 0..GARBAGE_DELAY_FRAMES {
+            side.step_frame();
+        }
+        side.apply_input(Input::HardDrop);
+        assert_eq!(3, side.board.count_garbage_rows());
+        assert_eq!(0, side.garbage.get_total());
+        assert!(!side.topped_out);
+    }
+}